@@ -0,0 +1,81 @@
+use crate::detect::weighted_length;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Groupings of source and target sentences an aligned pair may have. Anything other than one to
+// one costs extra, so it's only chosen when the lengths clearly call for it.
+const BEADS: &[(usize, usize, f32)] = &[
+    (1, 1, 0.0),
+    (1, 2, 0.3),
+    (2, 1, 0.3),
+    (2, 2, 0.6),
+    (1, 0, 1.0),
+    (0, 1, 1.0),
+];
+
+fn sentences(text: &str) -> Vec<&str> {
+    text.split_sentence_bounds()
+        .filter(|sentence| !sentence.trim().is_empty())
+        .collect()
+}
+
+/// Pairs source sentences with their translations by length, in order. A heuristic.
+pub fn align_sentences(source: &str, target: &str) -> Vec<(String, String)> {
+    let source_sentences = sentences(source);
+    let target_sentences = sentences(target);
+    let (n, m) = (source_sentences.len(), target_sentences.len());
+    if n == 0 || m == 0 {
+        return vec![(source.trim().to_string(), target.trim().to_string())];
+    }
+
+    let source_lengths: Vec<f32> = source_sentences
+        .iter()
+        .map(|s| weighted_length(s))
+        .collect();
+    let target_lengths: Vec<f32> = target_sentences
+        .iter()
+        .map(|s| weighted_length(s))
+        .collect();
+    // How much longer the target runs overall, so a pair is judged against the text's own ratio.
+    let ratio = target_lengths.iter().sum::<f32>() / source_lengths.iter().sum::<f32>().max(1.0);
+
+    // best[i][j] is the cheapest alignment of the first i source and j target sentences, with
+    // the bead that ended it.
+    let mut best: Vec<Vec<Option<(f32, usize)>>> = vec![vec![None; m + 1]; n + 1];
+    best[0][0] = Some((0.0, 0));
+    for i in 0..=n {
+        for j in 0..=m {
+            let Some((cost_so_far, _)) = best[i][j] else {
+                continue;
+            };
+            for (bead, (di, dj, penalty)) in BEADS.iter().enumerate() {
+                let (ni, nj) = (i + di, j + dj);
+                if ni > n || nj > m {
+                    continue;
+                }
+                let source_length: f32 = source_lengths[i..ni].iter().sum::<f32>() * ratio;
+                let target_length: f32 = target_lengths[j..nj].iter().sum();
+                let mismatch = (source_length - target_length).abs()
+                    / (source_length + target_length).max(1.0);
+                let cost = cost_so_far + mismatch + penalty;
+                if best[ni][nj].is_none_or(|(existing, _)| cost < existing) {
+                    best[ni][nj] = Some((cost, bead));
+                }
+            }
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        let (_, bead) = best[i][j].expect("every cell is reachable through the 1-0 and 0-1 beads");
+        let (di, dj, _) = BEADS[bead];
+        pairs.push((
+            source_sentences[i - di..i].concat().trim().to_string(),
+            target_sentences[j - dj..j].concat().trim().to_string(),
+        ));
+        i -= di;
+        j -= dj;
+    }
+    pairs.reverse();
+    pairs
+}
@@ -0,0 +1,199 @@
+use crate::openrouter::{OpenRouterClient, OpenRouterError};
+use crate::{FailureClass, TranslationSource};
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use tracing::warn;
+
+/// A model's answer to a prompt.
+#[derive(Clone, Debug)]
+pub struct Completion {
+    pub model: String,
+    pub text: String,
+    /// In dollars.
+    pub cost: f64,
+    /// The model stopped at its output length limit, so the text is likely cut short.
+    pub truncated: bool,
+    /// Prompt plus completion tokens, if the provider reported them.
+    pub tokens: Option<u32>,
+}
+
+/// The completion, or why there isn't one.
+pub type CompletionFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Completion, BackendError>> + Send + 'a>>;
+
+/// Where prompts are sent; [`OpenRouterClient`] by default.
+pub trait CompletionBackend: fmt::Debug + Send + Sync {
+    fn complete<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        main_prompt: &'a str,
+        model: &'a str,
+        temperature: f32,
+        seed: Option<u64>,
+    ) -> CompletionFuture<'a>;
+
+    /// Answers with the first of `models` that's available.
+    fn complete_any<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        main_prompt: &'a str,
+        models: &'a [&'a str],
+        temperature: f32,
+        seed: Option<u64>,
+    ) -> CompletionFuture<'a> {
+        Box::pin(complete_with_fallback(
+            self,
+            system_prompt,
+            main_prompt,
+            models,
+            temperature,
+            seed,
+        ))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BackendError {
+    pub class: FailureClass,
+    pub message: String,
+}
+
+impl BackendError {
+    pub fn new(class: FailureClass, message: impl Into<String>) -> Self {
+        Self {
+            class,
+            message: message.into(),
+        }
+    }
+
+    pub fn failure_class(&self) -> FailureClass {
+        self.class
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for BackendError {}
+
+impl From<OpenRouterError> for BackendError {
+    fn from(e: OpenRouterError) -> Self {
+        BackendError::new(e.failure_class(), e.to_string())
+    }
+}
+
+impl CompletionBackend for OpenRouterClient {
+    fn complete<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        main_prompt: &'a str,
+        model: &'a str,
+        temperature: f32,
+        seed: Option<u64>,
+    ) -> CompletionFuture<'a> {
+        Box::pin(async move {
+            OpenRouterClient::complete_any(
+                self,
+                system_prompt,
+                main_prompt,
+                &[model],
+                temperature,
+                seed,
+            )
+            .await
+            .map_err(BackendError::from)
+        })
+    }
+
+    fn complete_any<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        main_prompt: &'a str,
+        models: &'a [&'a str],
+        temperature: f32,
+        seed: Option<u64>,
+    ) -> CompletionFuture<'a> {
+        Box::pin(async move {
+            OpenRouterClient::complete_any(
+                self,
+                system_prompt,
+                main_prompt,
+                models,
+                temperature,
+                seed,
+            )
+            .await
+            .map_err(BackendError::from)
+        })
+    }
+}
+
+/// Tries each model in turn, returning the one that succeeded.
+pub(crate) async fn complete_with_fallback<B: CompletionBackend + ?Sized>(
+    backend: &B,
+    system_prompt: &str,
+    main_prompt: &str,
+    models: &[&str],
+    temperature: f32,
+    seed: Option<u64>,
+) -> Result<Completion, BackendError> {
+    let mut last_error = BackendError::new(FailureClass::Other, "No models to try");
+    for (i, model) in models.iter().enumerate() {
+        match backend
+            .complete(system_prompt, main_prompt, model, temperature, seed)
+            .await
+        {
+            Ok(completion) => return Ok(completion),
+            Err(e) => {
+                if let Some(next) = models.get(i + 1) {
+                    warn!("{} failed ({}), falling back to {}", model, e, next);
+                }
+                last_error = e;
+            }
+        }
+    }
+    Err(last_error)
+}
+
+// Sends the prompts to the source's models, bar the excluded ones, in the way the source asks
+// for.
+pub(crate) async fn complete_source(
+    backend: &dyn CompletionBackend,
+    source: TranslationSource,
+    system_prompt: &str,
+    main_prompt: &str,
+    temperature: f32,
+    seed: Option<u64>,
+    exclude: &[&str],
+) -> Result<Completion, BackendError> {
+    let models: Vec<&str> = source
+        .models()
+        .iter()
+        .copied()
+        .filter(|model| !exclude.contains(model))
+        .collect();
+    let models = models.as_slice();
+    match source {
+        TranslationSource::OpenrouterAny(_) => {
+            backend
+                .complete_any(system_prompt, main_prompt, models, temperature, seed)
+                .await
+        }
+        _ => {
+            complete_with_fallback(
+                backend,
+                system_prompt,
+                main_prompt,
+                models,
+                temperature,
+                seed,
+            )
+            .await
+        }
+    }
+}
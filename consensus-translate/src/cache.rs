@@ -0,0 +1,314 @@
+use crate::{HeuristicTokenizer, TranslationConfig, TranslationResponse};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Counters for a [`TranslationCache`], to judge whether its capacity is worth it.
+#[derive(Clone, Copy, Serialize, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries dropped to make room for new ones.
+    pub evictions: u64,
+    pub size: usize,
+}
+
+struct CacheEntry {
+    response: TranslationResponse,
+    last_used: u64,
+}
+
+struct CacheState {
+    capacity: usize,
+    entries: HashMap<String, CacheEntry>,
+    // Bumped on every access, so the smallest `last_used` is the least recently used entry.
+    clock: u64,
+    stats: CacheStats,
+}
+
+/// In-memory cache of complete responses. Clones share entries.
+#[derive(Clone)]
+pub struct TranslationCache {
+    state: Arc<Mutex<CacheState>>,
+}
+
+impl TranslationCache {
+    /// Holds up to `capacity` responses, evicting the least recently used beyond that.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(CacheState {
+                capacity,
+                entries: HashMap::new(),
+                clock: 0,
+                stats: CacheStats::default(),
+            })),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let state = self.state.lock().unwrap();
+        CacheStats {
+            size: state.entries.len(),
+            ..state.stats
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<TranslationResponse> {
+        let mut state = self.state.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        let response = state.entries.get_mut(key).map(|entry| {
+            entry.last_used = clock;
+            entry.response.clone()
+        });
+        if response.is_some() {
+            state.stats.hits += 1;
+            debug!(hits = state.stats.hits, "Translation cache hit");
+        } else {
+            state.stats.misses += 1;
+            debug!(misses = state.stats.misses, "Translation cache miss");
+        }
+        response
+    }
+
+    pub(crate) fn insert(&self, key: String, response: TranslationResponse) {
+        let mut state = self.state.lock().unwrap();
+        if state.capacity == 0 {
+            return;
+        }
+        state.clock += 1;
+        let last_used = state.clock;
+        if !state.entries.contains_key(&key) && state.entries.len() >= state.capacity {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                state.entries.remove(&oldest);
+                state.stats.evictions += 1;
+                debug!(
+                    evictions = state.stats.evictions,
+                    "Translation cache eviction"
+                );
+            }
+        }
+        state.entries.insert(
+            key,
+            CacheEntry {
+                response,
+                last_used,
+            },
+        );
+    }
+}
+
+impl fmt::Debug for TranslationCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TranslationCache")
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+// Everything about a request that can change its response. Settings that only affect how it's
+// sent, logged or cut short are left out, and defaulted ones are keyed on the value used, so
+// leaving a setting at its default and setting the default explicitly share entries.
+pub(crate) fn cache_key(sentence: &str, config: &TranslationConfig) -> String {
+    let TranslationConfig {
+        target_lang,
+        source_lang,
+        translation_type,
+        translation_style,
+        formality,
+        newline_policy,
+        bias,
+        conservatism,
+        eval_criteria,
+        candidate_order: _,
+        seed: _,
+        translate_temperature: _,
+        temperature_spread: _,
+        eval_temperature: _,
+        eval_max_candidates,
+        deterministic: _,
+        request_timeout: _,
+        prompt_caching: _,
+        max_cost_thousandths_cent: _,
+        max_total_tokens: _,
+        currency,
+        keep_refusals,
+        prompt_hardening,
+        failure_policy: _,
+        truncated_outputs,
+        synthesis_placement,
+        deadline: _,
+        translate_sources,
+        self_consistency,
+        max_sources,
+        priority,
+        duplicate_sources,
+        eval_source,
+        eval_models,
+        exclude_models,
+        #[cfg(feature = "deepl")]
+            deepl_api_key: _,
+        early_quorum,
+        min_distinct_candidates: _,
+        require_agreement,
+        length_ratio_guard,
+        output_filters,
+        output_must_match,
+        format_violations,
+        synthesizer,
+        tokenizer,
+        context,
+        domain,
+        audience,
+        dialect: _,
+        glossary,
+        comment_syntax,
+        pair_guidance,
+        max_concurrent_entries: _,
+        batch_stop_on_error: _,
+        batch_max_cost_thousandths_cent: _,
+        check_language,
+        reprompt_on_extraction_failure: _,
+        strip_wrapping_quotes,
+        ignored_candidates,
+        client: _,
+        key_resolver: _,
+        backend: _,
+        record_mode: _,
+        on_event: _,
+        sensitive_logs: _,
+        include_prompts,
+        trace,
+        preserve_register,
+        include_literal,
+        align_sentences,
+        assess_difficulty,
+        cache: _,
+        rate_limiter: _,
+        skip_untranslatable: _,
+    } = config;
+
+    let parts: [String; 52] = [
+        format!("{:?}", sentence),
+        format!("{:?}", target_lang),
+        format!("{:?}", source_lang),
+        format!("{:?}", translation_type),
+        format!("{:?}", translation_style),
+        format!("{:?}", formality),
+        format!("{:?}", newline_policy),
+        format!("{:?}", bias),
+        format!("{:?}", conservatism.clamp(0.0, 1.0)),
+        format!("{:?}", eval_criteria),
+        format!("{:?}", config.effective_candidate_order()),
+        format!("{:?}", config.effective_seed()),
+        format!("{:?}", config.effective_translate_temperature()),
+        format!(
+            "{:?}",
+            config.source_temperature(0, 2)..=config.source_temperature(1, 2)
+        ),
+        format!("{:?}", config.effective_eval_temperature()),
+        format!("{:?}", eval_max_candidates),
+        format!("{:?}", keep_refusals),
+        format!("{:?}", prompt_hardening),
+        format!("{:?}", strip_wrapping_quotes),
+        format!("{:?}", output_must_match),
+        format!("{:?}", format_violations),
+        format!("{:?}", currency),
+        format!("{:?}", truncated_outputs),
+        format!("{:?}", synthesis_placement),
+        format!("{:?}", translate_sources),
+        format!("{:?}", self_consistency),
+        format!("{:?}", max_sources),
+        format!("{:?}", priority),
+        format!("{:?}", duplicate_sources),
+        format!("{:?}", eval_source),
+        format!("{:?}", eval_models),
+        format!("{:?}", {
+            let mut exclude_models = exclude_models.clone();
+            exclude_models.sort_unstable();
+            exclude_models
+        }),
+        format!("{:?}", early_quorum),
+        format!("{:?}", config.effective_min_distinct_candidates()),
+        format!("{:?}", require_agreement),
+        format!("{:?}", length_ratio_guard),
+        format!("{:?}", output_filters),
+        format!("{:?}", synthesizer),
+        match tokenizer {
+            Some(tokenizer) => format!("{:?}", tokenizer),
+            None => format!("{:?}", HeuristicTokenizer),
+        },
+        format!("{:?}", context),
+        format!("{:?}", (domain, audience, glossary)),
+        format!("{:?}", config.effective_dialect()),
+        format!("{:?}", comment_syntax),
+        format!("{:?}", pair_guidance),
+        format!("{:?}", check_language),
+        format!("{:?}", ignored_candidates),
+        format!("{:?}", include_prompts),
+        format!("{:?}", trace),
+        format!("{:?}", preserve_register),
+        format!("{:?}", include_literal),
+        format!("{:?}", align_sentences),
+        format!("{:?}", assess_difficulty),
+    ];
+    parts.join("\u{1f}")
+}
+
+/// Stable hex SHA-256 of everything a [`TranslationCache`] keys on.
+pub fn request_fingerprint(sentence: &str, config: &TranslationConfig) -> String {
+    Sha256::digest(cache_key(sentence, config).as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_glossary_misses_the_cache() {
+        let sentence = "The bank was steep.";
+        let config = TranslationConfig {
+            glossary: vec![("bank".to_string(), "Ufer".to_string())],
+            ..TranslationConfig::default()
+        };
+        let other_entry = TranslationConfig {
+            glossary: vec![("bank".to_string(), "Bank".to_string())],
+            ..config.clone()
+        };
+        let no_glossary = TranslationConfig {
+            glossary: Vec::new(),
+            ..config.clone()
+        };
+        let response =
+            crate::untranslated_response(&crate::PipelineContext::new(sentence, "", &config));
+
+        let cache = TranslationCache::new(4);
+        cache.insert(cache_key(sentence, &config), response);
+
+        assert!(cache.get(&cache_key(sentence, &other_entry)).is_none());
+        assert!(cache.get(&cache_key(sentence, &no_glossary)).is_none());
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 0,
+                misses: 2,
+                evictions: 0,
+                size: 1,
+            }
+        );
+
+        assert!(cache.get(&cache_key(sentence, &config)).is_some());
+        assert_eq!(cache.stats().hits, 1);
+    }
+}
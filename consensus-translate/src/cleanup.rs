@@ -0,0 +1,129 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+// Wrapping some models put around translations despite being told not to.
+#[derive(Clone, Copy, Debug)]
+enum Cleanup {
+    // A leading "Here is the translation:" or the like, e.g.
+    // "Okay, here's the translation:\n\nHallo Welt".
+    Preamble,
+    // The whole output in bold or italics, e.g. "**Hallo Welt**".
+    Emphasis,
+}
+
+// Keyed on model slug prefixes. Only models seen doing it are listed, as the rules could eat
+// legitimate text from others.
+const MODEL_CLEANUPS: &[(&str, &[Cleanup])] = &[
+    ("google/gemini", &[Cleanup::Preamble, Cleanup::Emphasis]),
+    ("google/gemma", &[Cleanup::Preamble, Cleanup::Emphasis]),
+];
+
+/// Strips known wrapping from `model`'s output, unless that would leave nothing.
+pub fn clean_output(model: &str, output: &str) -> String {
+    let Some((_, cleanups)) = MODEL_CLEANUPS
+        .iter()
+        .find(|(prefix, _)| model.starts_with(prefix))
+    else {
+        return output.to_string();
+    };
+
+    let mut cleaned = output.trim().to_string();
+    for cleanup in *cleanups {
+        let stripped = match cleanup {
+            Cleanup::Preamble => strip_preamble(&cleaned),
+            Cleanup::Emphasis => strip_emphasis(&cleaned),
+        };
+        if !stripped.trim().is_empty() {
+            cleaned = stripped.trim().to_string();
+        }
+    }
+    cleaned
+}
+
+static PREAMBLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:(?:sure|okay|ok|of course|certainly)[,!.]?\s*)?(?:here(?:'s| is) (?:the |your |a )?(?:\w+ )?translation(?: (?:in|into|to) [\w ()]+)?|translation)\s*:\s*",
+    )
+    .unwrap()
+});
+
+fn strip_preamble(text: &str) -> String {
+    PREAMBLE.replace(text, "").into_owned()
+}
+
+fn strip_emphasis(text: &str) -> String {
+    for marker in ["**", "__", "*", "_"] {
+        if let Some(inner) = text
+            .strip_prefix(marker)
+            .and_then(|rest| rest.strip_suffix(marker))
+        {
+            if !inner.contains(marker) {
+                return inner.to_string();
+            }
+        }
+    }
+    text.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cleans_known_model_wrapping() {
+        // (model, raw output, cleaned output)
+        let fixtures = [
+            (
+                "google/gemini-2.5-flash",
+                "Okay, here's the translation:\n\nHallo Welt",
+                "Hallo Welt",
+            ),
+            (
+                "google/gemini-2.5-flash",
+                "Here is the German translation:\n\n*Wie geht es dir?*",
+                "Wie geht es dir?",
+            ),
+            (
+                "google/gemini-2.5-flash",
+                "Sure! Here's the translation into French (formal): Bonjour, madame.",
+                "Bonjour, madame.",
+            ),
+            (
+                "google/gemma-3-27b-it",
+                "**Guten Morgen!**",
+                "Guten Morgen!",
+            ),
+            (
+                "google/gemma-3-27b-it",
+                "Translation: Hola mundo",
+                "Hola mundo",
+            ),
+            ("google/gemma-3-27b-it", "__Ciao__\n", "Ciao"),
+        ];
+        for (model, raw, cleaned) in fixtures {
+            assert_eq!(clean_output(model, raw), cleaned, "{}: {:?}", model, raw);
+        }
+    }
+
+    #[test]
+    fn leaves_legitimate_text_alone() {
+        let fixtures = [
+            // Not a model known to wrap its output.
+            ("openai/gpt-4.1", "**Hallo**"),
+            ("openai/gpt-4.1", "Translation: Übersetzung"),
+            // Emphasis on part of the text only.
+            ("google/gemini-2.5-flash", "Die **wichtige** Frage"),
+            ("google/gemini-2.5-flash", "*Fußnote* und *Anmerkung*"),
+            // Stripping would leave nothing.
+            ("google/gemma-3-27b-it", "**"),
+            // The word "translation" inside the text.
+            (
+                "google/gemini-2.5-flash",
+                "Die Übersetzung: ein schwieriges Handwerk",
+            ),
+        ];
+        for (model, raw) in fixtures {
+            assert_eq!(clean_output(model, raw), raw, "{}: {:?}", model, raw);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+/// Comment delimiters; comments pass through untranslated.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CommentSyntax {
+    /// Starts a comment running to the end of the line, e.g. "//" or "#".
+    pub line: Vec<String>,
+    /// e.g. ("/*", "*/"). An unclosed one runs to the end.
+    pub block: Vec<(String, String)>,
+}
+
+impl CommentSyntax {
+    /// `// line` and `/* block */` comments.
+    pub fn c_like() -> Self {
+        Self {
+            line: vec!["//".to_string()],
+            block: vec![("/*".to_string(), "*/".to_string())],
+        }
+    }
+
+    /// `<!-- block -->` comments.
+    pub fn html() -> Self {
+        Self {
+            line: Vec::new(),
+            block: vec![("<!--".to_string(), "-->".to_string())],
+        }
+    }
+
+    // The byte range of the first comment at or after `from`.
+    fn next_comment(&self, text: &str, from: usize) -> Option<(usize, usize)> {
+        let rest = &text[from..];
+        let line = self
+            .line
+            .iter()
+            .filter(|start| !start.is_empty())
+            .filter_map(|start| rest.find(start.as_str()))
+            .map(|start| {
+                let end = rest[start..]
+                    .find('\n')
+                    .map_or(rest.len(), |end| start + end);
+                (start, end)
+            });
+        let block = self
+            .block
+            .iter()
+            .filter(|(open, _)| !open.is_empty())
+            .filter_map(|(open, close)| {
+                let start = rest.find(open.as_str())?;
+                let body = start + open.len();
+                let end = if close.is_empty() {
+                    rest.len()
+                } else {
+                    rest[body..]
+                        .find(close.as_str())
+                        .map_or(rest.len(), |end| body + end + close.len())
+                };
+                Some((start, end))
+            });
+        line.chain(block)
+            .min_by_key(|(start, _)| *start)
+            .map(|(start, end)| (from + start, from + end))
+    }
+}
+
+// Stands in for comment `index`, in brackets unlikely to occur in real text.
+fn marker(index: usize) -> String {
+    format!("⟦{}⟧", index)
+}
+
+// Whether `text` has had comments swapped out.
+pub fn has_markers(text: &str) -> bool {
+    text.contains('⟦')
+}
+
+pub struct ProtectedText {
+    pub text: String,
+    comments: Vec<String>,
+}
+
+impl ProtectedText {
+    // Puts the comments back into a model's output, returning it with the comments whose marker
+    // it lost.
+    pub fn restore(&self, output: &str) -> (String, Vec<String>) {
+        let mut restored = output.to_string();
+        let mut missing = Vec::new();
+        for (index, comment) in self.comments.iter().enumerate() {
+            let marker = marker(index);
+            if !restored.contains(&marker) {
+                missing.push(comment.clone());
+            }
+            restored = restored.replace(&marker, comment);
+        }
+        (restored, missing)
+    }
+
+    // Swaps the comments in a text `restore` produced back for their markers.
+    pub fn protect_output(&self, output: &str) -> String {
+        let mut protected = output.to_string();
+        for (index, comment) in self.comments.iter().enumerate() {
+            protected = protected.replace(comment.as_str(), &marker(index));
+        }
+        protected
+    }
+}
+
+pub fn protect(text: &str, syntax: &CommentSyntax) -> ProtectedText {
+    let mut protected = String::new();
+    let mut comments = Vec::new();
+    let mut position = 0;
+    while let Some((start, end)) = syntax.next_comment(text, position) {
+        protected.push_str(&text[position..start]);
+        protected.push_str(&marker(comments.len()));
+        comments.push(text[start..end].to_string());
+        position = end;
+    }
+    protected.push_str(&text[position..]);
+    ProtectedText {
+        text: protected,
+        comments,
+    }
+}
@@ -0,0 +1,116 @@
+use serde::{Deserialize, Serialize};
+
+/// What a provider charges for, so all costs come out in dollars.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CostBasis {
+    /// Dollars per million tokens. Cached tokens are billed at the cached rate.
+    PerToken {
+        input_per_million: f64,
+        cached_input_per_million: f64,
+        output_per_million: f64,
+    },
+    /// Dollars per million characters of source text, as DeepL and Google Translate bill.
+    PerCharacter { per_million: f64 },
+}
+
+/// The unit an item's cost was billed in.
+#[derive(Clone, Copy, Serialize, Debug, Default, PartialEq, Eq)]
+pub enum CostUnit {
+    #[default]
+    Tokens,
+    Characters,
+}
+
+/// What a request used, in whichever units the provider reports.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BilledUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub cached_tokens: u32,
+    pub characters: u32,
+}
+
+impl CostBasis {
+    pub fn unit(&self) -> CostUnit {
+        match self {
+            CostBasis::PerToken { .. } => CostUnit::Tokens,
+            CostBasis::PerCharacter { .. } => CostUnit::Characters,
+        }
+    }
+
+    /// In dollars.
+    pub fn cost(&self, usage: &BilledUsage) -> f64 {
+        match *self {
+            CostBasis::PerToken {
+                input_per_million,
+                cached_input_per_million,
+                output_per_million,
+            } => {
+                let cached_tokens = usage.cached_tokens.min(usage.prompt_tokens);
+                let uncached_tokens = usage.prompt_tokens - cached_tokens;
+                (uncached_tokens as f64 * input_per_million
+                    + cached_tokens as f64 * cached_input_per_million
+                    + usage.completion_tokens as f64 * output_per_million)
+                    / 1_000_000.0
+            }
+            CostBasis::PerCharacter { per_million } => {
+                usage.characters as f64 * per_million / 1_000_000.0
+            }
+        }
+    }
+}
+
+/// A currency to report costs in; see [`convert_from_usd`].
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub enum Currency {
+    #[default]
+    Usd,
+    /// Euros, at this many to the dollar.
+    Eur { per_usd: f64 },
+}
+
+impl Currency {
+    /// The ISO 4217 code, e.g. "EUR".
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::Usd => "USD",
+            Currency::Eur { .. } => "EUR",
+        }
+    }
+}
+
+/// `dollars` in `currency`, unrounded.
+pub fn convert_from_usd(dollars: f64, currency: Currency) -> f64 {
+    match currency {
+        Currency::Usd => dollars,
+        Currency::Eur { per_usd } => dollars * per_usd,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usd_is_unchanged() {
+        assert_eq!(convert_from_usd(1.25, Currency::Usd), 1.25);
+        assert_eq!(Currency::Usd.code(), "USD");
+    }
+
+    #[test]
+    fn eur_uses_the_given_rate() {
+        let currency = Currency::Eur { per_usd: 0.92 };
+        assert!((convert_from_usd(10.0, currency) - 9.2).abs() < 1e-12);
+        assert_eq!(convert_from_usd(0.0, currency), 0.0);
+        assert_eq!(currency.code(), "EUR");
+    }
+
+    #[test]
+    fn conversion_is_not_rounded() {
+        // Fractions of a cent, as single calls cost, must survive the conversion.
+        let dollars = 0.000_123_456;
+        assert_eq!(convert_from_usd(dollars, Currency::Usd), dollars);
+        let euros = convert_from_usd(dollars, Currency::Eur { per_usd: 0.9 });
+        assert!((euros - 0.000_111_110_4).abs() < 1e-15, "{}", euros);
+    }
+}
@@ -1,7 +1,9 @@
-use crate::Formality;
-use reqwest::Client;
+use crate::cost::{BilledUsage, CostBasis};
+use crate::{FailureClass, Formality, Language};
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
+use std::fmt;
 
 #[derive(Serialize)]
 struct TranslateRequest {
@@ -22,6 +24,64 @@ struct Translation {
     text: String,
 }
 
+#[derive(Deserialize)]
+struct ErrorResponse {
+    message: String,
+}
+
+// DeepL API Pro. Free keys, which end in ":fx", aren't billed.
+const PRO_PRICING: CostBasis = CostBasis::PerCharacter { per_million: 25.0 };
+const FREE_PRICING: CostBasis = CostBasis::PerCharacter { per_million: 0.0 };
+
+#[derive(Debug)]
+pub enum DeepLError {
+    Request(reqwest::Error),
+    Api { status: StatusCode, message: String },
+    UnsupportedLanguage(Language),
+    InvalidResponse(String),
+}
+
+impl DeepLError {
+    pub fn failure_class(&self) -> FailureClass {
+        match self {
+            DeepLError::Request(e) if e.is_timeout() => FailureClass::Timeout,
+            DeepLError::Request(e) if e.is_decode() => FailureClass::ParseError,
+            DeepLError::Request(_) => FailureClass::Network,
+            DeepLError::Api { status, .. } if *status == StatusCode::TOO_MANY_REQUESTS => {
+                FailureClass::RateLimit
+            }
+            DeepLError::Api { status, .. } if status.is_client_error() => FailureClass::Http4xx,
+            DeepLError::Api { status, .. } if status.is_server_error() => FailureClass::Http5xx,
+            DeepLError::Api { .. } | DeepLError::UnsupportedLanguage(_) => FailureClass::Other,
+            DeepLError::InvalidResponse(_) => FailureClass::ParseError,
+        }
+    }
+}
+
+impl fmt::Display for DeepLError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeepLError::Request(e) => write!(f, "Request failed: {}", e),
+            DeepLError::Api { status, message } => {
+                write!(f, "DeepL API error: {} (status: {})", message, status)
+            }
+            DeepLError::UnsupportedLanguage(lang) => {
+                write!(f, "DeepL doesn't support {}", lang.to_llm_format())
+            }
+            DeepLError::InvalidResponse(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for DeepLError {}
+
+impl From<reqwest::Error> for DeepLError {
+    fn from(e: reqwest::Error) -> Self {
+        DeepLError::Request(e)
+    }
+}
+
+#[derive(Clone)]
 pub struct DeepLClient {
     api_key: String,
     base_url: String,
@@ -37,23 +97,54 @@ impl DeepLClient {
         }
     }
 
+    /// Picks the free or Pro endpoint from the key, as DeepL does.
+    pub fn for_key(api_key: &str) -> Self {
+        let base_url = if Self::is_free_key(api_key) {
+            "https://api-free.deepl.com/v2"
+        } else {
+            "https://api.deepl.com/v2"
+        };
+        Self::new(api_key, base_url)
+    }
+
+    fn is_free_key(api_key: &str) -> bool {
+        api_key.ends_with(":fx")
+    }
+
+    pub fn pricing(&self) -> CostBasis {
+        if Self::is_free_key(&self.api_key) {
+            FREE_PRICING
+        } else {
+            PRO_PRICING
+        }
+    }
+
+    /// Returns the translation and its cost in dollars.
     pub async fn translate(
         &self,
         text: &str,
-        target_lang: &str,
-        source_lang: Option<&str>,
+        target_lang: Language,
+        source_lang: Option<Language>,
         formality: Formality,
-    ) -> Result<String, Box<dyn Error>> {
+    ) -> Result<(String, f64), DeepLError> {
         let url = format!("{}/translate", self.base_url);
+        let target_code = target_lang
+            .to_deepl_code()
+            .ok_or(DeepLError::UnsupportedLanguage(target_lang))?;
+        // Source codes don't take a variant, e.g. "PT" rather than "PT-BR".
+        let source_code = source_lang
+            .and_then(|lang| lang.to_deepl_code())
+            .map(|code| code.split('-').next().unwrap_or(code).to_string());
+        // The "prefer_" forms fall back to the default for languages without formality.
         let formality_str = match formality {
-            Formality::LessFormal => "less".to_string(),
-            Formality::NormalFormality => "default".to_string(),
-            Formality::MoreFormal => "more".to_string(),
+            Formality::LessFormal => "prefer_less".to_string(),
+            Formality::NormalFormality | Formality::MatchSource => "default".to_string(),
+            Formality::MoreFormal => "prefer_more".to_string(),
         };
         let request_body = TranslateRequest {
             text: vec![text.to_string()],
-            target_lang: target_lang.to_string(),
-            source_lang: source_lang.map(|s| s.to_string()),
+            target_lang: target_code.to_string(),
+            source_lang: source_code,
             formality: formality_str,
         };
 
@@ -66,11 +157,36 @@ impl DeepLClient {
             .send()
             .await?;
 
-        let translate_response: TranslateResponse = response.json().await?;
-        if translate_response.translations.is_empty() {
-            return Err("No translations returned from DeepL API".into());
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await?;
+            let message = serde_json::from_str::<ErrorResponse>(&body)
+                .map(|e| e.message)
+                .unwrap_or(body);
+            return Err(DeepLError::Api { status, message });
         }
 
-        Ok(translate_response.translations[0].text.clone())
+        let translate_response: TranslateResponse = response.json().await?;
+        let Some(translation) = translate_response.translations.into_iter().next() else {
+            return Err(DeepLError::InvalidResponse(
+                "No translations returned from DeepL API".to_string(),
+            ));
+        };
+
+        let cost = self.pricing().cost(&BilledUsage {
+            characters: text.chars().count() as u32,
+            ..Default::default()
+        });
+
+        Ok((translation.text, cost))
+    }
+}
+
+// Leaves out the API key.
+impl fmt::Debug for DeepLClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeepLClient")
+            .field("base_url", &self.base_url)
+            .finish_non_exhaustive()
     }
 }
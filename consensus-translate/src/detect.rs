@@ -0,0 +1,426 @@
+use crate::languages::Script;
+use crate::stats::count_words;
+use crate::Language;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use unicode_segmentation::UnicodeSegmentation;
+
+pub(crate) fn script_of(c: char) -> Option<Script> {
+    match c as u32 {
+        0x0041..=0x024F | 0x1E00..=0x1EFF => Some(Script::Latin),
+        0x0370..=0x03FF | 0x1F00..=0x1FFF => Some(Script::Greek),
+        0x0400..=0x052F => Some(Script::Cyrillic),
+        0x0590..=0x05FF => Some(Script::Hebrew),
+        0x0600..=0x06FF | 0x0750..=0x077F | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => {
+            Some(Script::Arabic)
+        }
+        0x0900..=0x097F => Some(Script::Devanagari),
+        0x0E00..=0x0E7F => Some(Script::Thai),
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Some(Script::Hangul),
+        0x3040..=0x30FF => Some(Script::Kana),
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF => Some(Script::Han),
+        _ => None,
+    }
+}
+
+// Whether `script` belongs in text written in `expected`, a language's own script. Japanese and
+// Korean text mix in Han characters.
+fn fits_script(script: Script, expected: Script) -> bool {
+    script == expected
+        || (script == Script::Han && matches!(expected, Script::Kana | Script::Hangul))
+}
+
+// Common function words, which tell Latin-script languages apart where the script can't.
+const STOPWORDS: &[(Language, &[&str])] = &[
+    (
+        Language::English,
+        &[
+            "the", "and", "is", "of", "to", "in", "that", "it", "with", "for",
+        ],
+    ),
+    (
+        Language::French,
+        &[
+            "le", "la", "les", "et", "est", "des", "une", "que", "pas", "dans", "pour", "je",
+        ],
+    ),
+    (
+        Language::German,
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ein", "eine", "ich", "mit", "zu", "auf",
+        ],
+    ),
+    (
+        Language::Spanish,
+        &[
+            "el", "los", "las", "y", "es", "una", "que", "por", "con", "para", "del", "no",
+        ],
+    ),
+    (
+        Language::Italian,
+        &[
+            "il", "gli", "e", "è", "di", "che", "un", "una", "non", "per", "con", "sono",
+        ],
+    ),
+    (
+        Language::Swedish,
+        &[
+            "och", "är", "att", "det", "en", "ett", "som", "inte", "jag", "på", "med", "för",
+        ],
+    ),
+    (
+        Language::Hungarian,
+        &[
+            "a", "az", "és", "hogy", "nem", "egy", "van", "is", "meg", "ez", "de", "csak",
+        ],
+    ),
+    (
+        Language::Esperanto,
+        &[
+            "la", "kaj", "estas", "de", "al", "mi", "ne", "ke", "en", "por", "kun", "ĉu",
+        ],
+    ),
+    (
+        Language::Vietnamese,
+        &[
+            "và", "là", "của", "không", "có", "những", "một", "được", "tôi", "này", "cho",
+        ],
+    ),
+    (
+        Language::Welsh,
+        &[
+            "y", "yr", "a", "ac", "yn", "mae", "ei", "i", "o", "ddim", "gyda", "hefyd",
+        ],
+    ),
+];
+
+// A guess needs at least this many function words, and more than any other language has.
+const MIN_STOPWORD_HITS: usize = 2;
+
+/// A guess at the language of a text, see [`detect_language_with_confidence`].
+#[derive(Clone, Serialize, Debug, PartialEq)]
+pub struct DetectionResult {
+    /// `Unknown` when the text doesn't give enough to go on.
+    pub language: Language,
+    /// 0.0 to 1.0, rough rather than calibrated.
+    pub confidence: f32,
+    /// Other languages the text could be in, with their confidence, most likely first.
+    pub alternatives: Vec<(Language, f32)>,
+}
+
+impl DetectionResult {
+    fn unknown(alternatives: Vec<(Language, f32)>) -> Self {
+        Self {
+            language: Language::Unknown,
+            confidence: 0.0,
+            alternatives,
+        }
+    }
+}
+
+// Confidence in a guess backed by `hits` function words: two gives 0.75, each more halves the
+// remaining doubt.
+fn stopword_evidence(hits: usize) -> f32 {
+    1.0 - 0.5f32.powi(hits as i32)
+}
+
+/// A rough guess at the language of `text`, or None.
+pub fn detect_language(text: &str) -> Option<Language> {
+    Some(detect_language_with_confidence(text).language).filter(|lang| *lang != Language::Unknown)
+}
+
+/// [`detect_language`]'s guess with a confidence and runners-up.
+pub fn detect_language_with_confidence(text: &str) -> DetectionResult {
+    let scripts: Vec<Script> = text
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .filter_map(script_of)
+        .collect();
+    let count = |script: Script| scripts.iter().filter(|s| **s == script).count();
+    let Some(dominant) = [
+        Script::Latin,
+        Script::Arabic,
+        Script::Hebrew,
+        Script::Greek,
+        Script::Cyrillic,
+        Script::Devanagari,
+        Script::Thai,
+        Script::Han,
+        Script::Kana,
+        Script::Hangul,
+    ]
+    .into_iter()
+    .max_by_key(|script| count(*script))
+    .filter(|script| count(*script) > 0) else {
+        return DetectionResult::unknown(Vec::new());
+    };
+    // How much of the text is in the script the guess rests on, counting kanji and kana together
+    // as Japanese mixes them.
+    let in_script = match dominant {
+        Script::Han | Script::Kana => count(Script::Han) + count(Script::Kana),
+        script => count(script),
+    };
+    let share = in_script as f32 / scripts.len() as f32;
+    let by_script =
+        |language: Language, confidence: f32, alternatives: Vec<(Language, f32)>| DetectionResult {
+            language,
+            confidence: share * confidence,
+            alternatives: alternatives
+                .into_iter()
+                .map(|(lang, confidence)| (lang, share * confidence))
+                .collect(),
+        };
+
+    match dominant {
+        // Any kana means Japanese, which mixes it with kanji.
+        Script::Han | Script::Kana if count(Script::Kana) > 0 => {
+            by_script(Language::Japanese, 1.0, Vec::new())
+        }
+        // Kanji-only Japanese is rare but short texts can be.
+        Script::Han => by_script(Language::Chinese, 0.9, vec![(Language::Japanese, 0.1)]),
+        Script::Kana => by_script(Language::Japanese, 1.0, Vec::new()),
+        Script::Hangul => by_script(Language::Korean, 1.0, Vec::new()),
+        Script::Thai => by_script(Language::Thai, 1.0, Vec::new()),
+        Script::Greek => by_script(Language::Greek, 1.0, Vec::new()),
+        Script::Hebrew => by_script(Language::Hebrew, 1.0, Vec::new()),
+        Script::Devanagari => by_script(Language::Hindi, 1.0, Vec::new()),
+        Script::Arabic => by_script(Language::Arabic, 1.0, Vec::new()),
+        Script::Cyrillic => {
+            let ukrainian = text
+                .chars()
+                .any(|c| matches!(c, 'і' | 'ї' | 'є' | 'ґ' | 'І' | 'Ї' | 'Є' | 'Ґ'));
+            if ukrainian {
+                by_script(Language::Ukrainian, 1.0, Vec::new())
+            } else {
+                // Short Ukrainian text may not use any of its own letters.
+                by_script(Language::Russian, 0.8, vec![(Language::Ukrainian, 0.2)])
+            }
+        }
+        Script::Latin => {
+            let words: Vec<String> = text.unicode_words().map(str::to_lowercase).collect();
+            let mut hits: Vec<(Language, usize)> = STOPWORDS
+                .iter()
+                .map(|(lang, stopwords)| {
+                    let hits = words
+                        .iter()
+                        .filter(|word| stopwords.contains(&word.as_str()))
+                        .count();
+                    (*lang, hits)
+                })
+                .collect();
+            hits.sort_by_key(|(_, hits)| std::cmp::Reverse(*hits));
+            let total: usize = hits.iter().map(|(_, hits)| hits).sum();
+            let confidence = |language_hits: usize| {
+                share * language_hits as f32 / total.max(1) as f32 * stopword_evidence(hits[0].1)
+            };
+            let mut candidates: Vec<(Language, f32)> = hits
+                .iter()
+                .filter(|(_, hits)| *hits > 0)
+                .map(|(lang, hits)| (*lang, confidence(*hits)))
+                .collect();
+            match hits.as_slice() {
+                [(lang, best), (_, second), ..] if *best >= MIN_STOPWORD_HITS && best > second => {
+                    let (_, confidence) = candidates.remove(0);
+                    DetectionResult {
+                        language: *lang,
+                        confidence,
+                        alternatives: candidates,
+                    }
+                }
+                _ => DetectionResult::unknown(candidates),
+            }
+        }
+    }
+}
+
+// Below this many words an unchanged output is as likely to be a name or a loanword as an echo.
+const MIN_ECHO_WORDS: usize = 4;
+
+/// Whether `output` is in the wrong script for `target_lang`, or copies `source`.
+pub fn is_wrong_language(source: &str, output: &str, target_lang: Language) -> bool {
+    let Some(expected) = target_lang.script() else {
+        return false;
+    };
+
+    let echoed = source.trim().to_lowercase() == output.trim().to_lowercase()
+        && count_words(source) >= MIN_ECHO_WORDS;
+    if echoed {
+        return true;
+    }
+
+    let scripts: Vec<Script> = output
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .filter_map(script_of)
+        .collect();
+    if scripts.is_empty() {
+        return false;
+    }
+    let in_expected = scripts
+        .iter()
+        .filter(|s| fits_script(**s, expected))
+        .count();
+
+    in_expected * 2 < scripts.len()
+}
+
+// CJK graphemes carry about as much as a short word, so they count as this many.
+const DENSE_GRAPHEME_WEIGHT: f32 = 3.0;
+
+/// Grapheme length, weighted to be comparable across scripts.
+pub fn weighted_length(text: &str) -> f32 {
+    text.graphemes(true)
+        .map(
+            |grapheme| match grapheme.chars().next().and_then(script_of) {
+                Some(Script::Han | Script::Kana | Script::Hangul) => DENSE_GRAPHEME_WEIGHT,
+                _ => 1.0,
+            },
+        )
+        .sum()
+}
+
+// Share of the words in play that may differ before the synthesis counts as shifted.
+const MAX_REGISTER_DRIFT: f32 = 0.25;
+
+fn word_set(text: &str) -> HashSet<String> {
+    text.unicode_words().map(str::to_lowercase).collect()
+}
+
+/// Whether `synthesis` drops words most candidates share, or adds ones none use.
+pub fn is_register_shift(candidates: &[&str], synthesis: &str) -> bool {
+    if candidates.len() < 2 {
+        return false;
+    }
+
+    let candidate_words: Vec<HashSet<String>> = candidates.iter().map(|c| word_set(c)).collect();
+    let synthesis_words = word_set(synthesis);
+
+    let mut all_words: HashSet<&String> = HashSet::new();
+    for words in &candidate_words {
+        all_words.extend(words);
+    }
+    let majority_words: Vec<&String> = all_words
+        .iter()
+        .copied()
+        .filter(|word| {
+            candidate_words.iter().filter(|w| w.contains(*word)).count() * 2 > candidates.len()
+        })
+        .collect();
+
+    let dropped = majority_words
+        .iter()
+        .filter(|word| !synthesis_words.contains(**word))
+        .count();
+    let added = synthesis_words
+        .iter()
+        .filter(|word| !all_words.contains(word))
+        .count();
+
+    let in_play = majority_words.len() + added;
+    in_play > 0 && (dropped + added) as f32 / in_play as f32 > MAX_REGISTER_DRIFT
+}
+
+/// Whether `text` holds only numbers, punctuation, symbols, URLs or emails.
+pub fn is_untranslatable(text: &str) -> bool {
+    text.split_whitespace()
+        .all(|word| LINK.is_match(word) || !word.chars().any(char::is_alphabetic))
+}
+
+// A URL or an email address.
+static LINK: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?i)(https?://|www\.)\S+$|^\S+@\S+\.\S+$").unwrap());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_french() {
+        let result = detect_language_with_confidence(
+            "Je ne sais pas si le train est déjà parti pour la gare du Nord.",
+        );
+        assert_eq!(result.language, Language::French);
+        assert!(result.confidence > 0.5, "{:?}", result);
+    }
+
+    #[test]
+    fn detects_english() {
+        let result =
+            detect_language_with_confidence("The weather is nice and we are going to the beach.");
+        assert_eq!(result.language, Language::English);
+        assert!(result.confidence > 0.5, "{:?}", result);
+        assert_eq!(
+            detect_language("I think that it is on the table"),
+            Some(Language::English)
+        );
+    }
+
+    #[test]
+    fn short_or_ambiguous_input_is_unknown() {
+        for text in ["Paris", "OK", "", "12345", "Taxi Hotel Restaurant"] {
+            let result = detect_language_with_confidence(text);
+            assert_eq!(result.language, Language::Unknown, "{}", text);
+            assert_eq!(result.confidence, 0.0);
+            assert_eq!(detect_language(text), None);
+        }
+    }
+
+    #[test]
+    fn untranslatable_numbers() {
+        assert!(is_untranslatable("42"));
+        assert!(is_untranslatable("3.14 -7 1,000"));
+    }
+
+    #[test]
+    fn untranslatable_punctuation() {
+        assert!(is_untranslatable("?!... -- ()"));
+        assert!(is_untranslatable("€ 20 %"));
+    }
+
+    #[test]
+    fn untranslatable_url() {
+        assert!(is_untranslatable("https://example.com/path?q=1"));
+        assert!(is_untranslatable("www.example.org"));
+    }
+
+    #[test]
+    fn untranslatable_email() {
+        assert!(is_untranslatable("someone@example.com"));
+    }
+
+    #[test]
+    fn untranslatable_empty() {
+        assert!(is_untranslatable(""));
+        assert!(is_untranslatable("  \n\t "));
+    }
+
+    #[test]
+    fn words_are_translatable() {
+        assert!(!is_untranslatable("Hello world"));
+        assert!(!is_untranslatable("see https://example.com"));
+        assert!(!is_untranslatable("42 apples"));
+    }
+
+    #[test]
+    fn wrong_language_follows_language_script() {
+        assert!(!is_wrong_language(
+            "Hello",
+            "東京へ行きます",
+            Language::Japanese
+        ));
+        assert!(!is_wrong_language(
+            "Hello",
+            "학교에 갑니다 學校",
+            Language::Korean
+        ));
+        assert!(is_wrong_language(
+            "Hello",
+            "とうきょうへいきます",
+            Language::Chinese
+        ));
+        assert!(is_wrong_language("Hello", "Привет мир", Language::German));
+        assert!(!is_wrong_language("Hello", "Привет мир", Language::Russian));
+    }
+}
@@ -0,0 +1,246 @@
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// One run of a word-level diff, spaces included.
+#[derive(Clone, Serialize, Debug, PartialEq, Eq)]
+pub enum DiffOp {
+    Equal(String),
+    Insert(String),
+    Delete(String),
+}
+
+/// Word-level shortest edit script (Myers, linear space).
+pub fn word_diff(old: &str, new: &str) -> Vec<DiffOp> {
+    let old_words: Vec<&str> = old.split_word_bounds().collect();
+    let new_words: Vec<&str> = new.split_word_bounds().collect();
+    let mut ops = Vec::new();
+    diff_words(&old_words, &new_words, &mut ops);
+    ops
+}
+
+// Appends a word to the ops, extending the last run if it's of the same kind.
+fn push_word(ops: &mut Vec<DiffOp>, op: DiffOp) {
+    match (ops.last_mut(), op) {
+        (Some(DiffOp::Equal(last)), DiffOp::Equal(word))
+        | (Some(DiffOp::Insert(last)), DiffOp::Insert(word))
+        | (Some(DiffOp::Delete(last)), DiffOp::Delete(word)) => last.push_str(&word),
+        (_, op) => ops.push(op),
+    }
+}
+
+fn diff_words(old: &[&str], new: &[&str], ops: &mut Vec<DiffOp>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    for word in &old[..prefix] {
+        push_word(ops, DiffOp::Equal(word.to_string()));
+    }
+    if old_middle.is_empty() || new_middle.is_empty() {
+        for word in old_middle {
+            push_word(ops, DiffOp::Delete(word.to_string()));
+        }
+        for word in new_middle {
+            push_word(ops, DiffOp::Insert(word.to_string()));
+        }
+    } else if let Some((x, y)) = middle_snake(old_middle, new_middle) {
+        diff_words(&old_middle[..x], &new_middle[..y], ops);
+        diff_words(&old_middle[x..], &new_middle[y..], ops);
+    } else {
+        for word in old_middle {
+            push_word(ops, DiffOp::Delete(word.to_string()));
+        }
+        for word in new_middle {
+            push_word(ops, DiffOp::Insert(word.to_string()));
+        }
+    }
+    for word in &old[old.len() - suffix..] {
+        push_word(ops, DiffOp::Equal(word.to_string()));
+    }
+}
+
+// Where the shortest edit script from `old` to `new` can be split in two, found by searching
+// from both ends at once until the paths meet. Both must be non-empty.
+fn middle_snake(old: &[&str], new: &[&str]) -> Option<(usize, usize)> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let max_d = (n + m + 1) / 2;
+    let offset = max_d;
+    let width = 2 * max_d + 2;
+    // forward[offset + k] is the furthest x reached on diagonal k = x - y from the start;
+    // backward the same from the end.
+    let mut forward = vec![-1isize; width as usize];
+    let mut backward = vec![-1isize; width as usize];
+    forward[(offset + 1) as usize] = 0;
+    backward[(offset + 1) as usize] = 0;
+    let delta = n - m;
+    // With an odd delta the forward search meets the backward one, otherwise the reverse.
+    let forward_meets = delta % 2 != 0;
+    let (mut k1_start, mut k1_end, mut k2_start, mut k2_end) = (0, 0, 0, 0);
+
+    for d in 0..max_d {
+        let mut k1 = -d + k1_start;
+        while k1 <= d - k1_end {
+            let k1_offset = (offset + k1) as usize;
+            let mut x1 = if k1 == -d || (k1 != d && forward[k1_offset - 1] < forward[k1_offset + 1])
+            {
+                forward[k1_offset + 1]
+            } else {
+                forward[k1_offset - 1] + 1
+            };
+            let mut y1 = x1 - k1;
+            while x1 < n && y1 < m && old[x1 as usize] == new[y1 as usize] {
+                x1 += 1;
+                y1 += 1;
+            }
+            forward[k1_offset] = x1;
+            if x1 > n {
+                k1_end += 2;
+            } else if y1 > m {
+                k1_start += 2;
+            } else if forward_meets {
+                let k2_offset = offset + delta - k1;
+                if (0..width).contains(&k2_offset) && backward[k2_offset as usize] != -1 {
+                    let x2 = n - backward[k2_offset as usize];
+                    if x1 >= x2 {
+                        return Some((x1 as usize, y1 as usize));
+                    }
+                }
+            }
+            k1 += 2;
+        }
+
+        let mut k2 = -d + k2_start;
+        while k2 <= d - k2_end {
+            let k2_offset = (offset + k2) as usize;
+            let mut x2 =
+                if k2 == -d || (k2 != d && backward[k2_offset - 1] < backward[k2_offset + 1]) {
+                    backward[k2_offset + 1]
+                } else {
+                    backward[k2_offset - 1] + 1
+                };
+            let mut y2 = x2 - k2;
+            while x2 < n && y2 < m && old[(n - x2 - 1) as usize] == new[(m - y2 - 1) as usize] {
+                x2 += 1;
+                y2 += 1;
+            }
+            backward[k2_offset] = x2;
+            if x2 > n {
+                k2_end += 2;
+            } else if y2 > m {
+                k2_start += 2;
+            } else if !forward_meets {
+                let k1_offset = offset + delta - k2;
+                if (0..width).contains(&k1_offset) && forward[k1_offset as usize] != -1 {
+                    let x1 = forward[k1_offset as usize];
+                    let y1 = offset + x1 - k1_offset;
+                    if x1 >= n - x2 {
+                        return Some((x1 as usize, y1 as usize));
+                    }
+                }
+            }
+            k2 += 2;
+        }
+    }
+    None
+}
+
+/// Byte ranges of `base` the `others` render differently, with the variants.
+pub fn disagreement_spans(base: &str, others: &[&str]) -> Vec<(usize, usize, Vec<String>)> {
+    // Candidates often agree word for word, so each distinct text is only diffed once.
+    let mut distinct: Vec<&str> = Vec::new();
+    for other in others {
+        if *other != base && !distinct.contains(other) {
+            distinct.push(other);
+        }
+    }
+    let diffs: Vec<Vec<DiffOp>> = distinct
+        .iter()
+        .map(|other| word_diff(base, other))
+        .collect();
+
+    let mut ranges: Vec<(usize, usize)> =
+        diffs.iter().flat_map(|ops| changed_ranges(ops)).collect();
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    merged
+        .into_iter()
+        .map(|(start, end)| {
+            let mut variants = vec![base[start..end].to_string()];
+            for ops in &diffs {
+                let variant = render_range(ops, start, end);
+                if !variants.contains(&variant) {
+                    variants.push(variant);
+                }
+            }
+            (start, end, variants)
+        })
+        .collect()
+}
+
+// The byte ranges of the old text covered by each run of changes.
+fn changed_ranges(ops: &[DiffOp]) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut position = 0;
+    let mut open: Option<usize> = None;
+    for op in ops {
+        match op {
+            DiffOp::Equal(text) => {
+                if let Some(start) = open.take() {
+                    ranges.push((start, position));
+                }
+                position += text.len();
+            }
+            DiffOp::Delete(text) => {
+                open.get_or_insert(position);
+                position += text.len();
+            }
+            DiffOp::Insert(_) => {
+                open.get_or_insert(position);
+            }
+        }
+    }
+    if let Some(start) = open {
+        ranges.push((start, position));
+    }
+    ranges
+}
+
+// What the new text has in place of the old text's `start..end`, which must lie on run
+// boundaries of `ops` or inside `Equal` runs.
+fn render_range(ops: &[DiffOp], start: usize, end: usize) -> String {
+    let mut rendered = String::new();
+    let mut position = 0;
+    for op in ops {
+        match op {
+            DiffOp::Equal(text) => {
+                let (from, to) = (start.max(position), end.min(position + text.len()));
+                if from < to {
+                    rendered.push_str(&text[from - position..to - position]);
+                }
+                position += text.len();
+            }
+            DiffOp::Delete(text) => position += text.len(),
+            DiffOp::Insert(text) => {
+                if (start..=end).contains(&position) {
+                    rendered.push_str(text);
+                }
+            }
+        }
+    }
+    rendered
+}
@@ -0,0 +1,36 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+// Descriptive names for the levels. The one earliest in the answer wins, so "upper
+// intermediate" is read whole rather than as "intermediate".
+const LEVEL_NAMES: &[(&str, &str)] = &[
+    ("upper intermediate", "B2"),
+    ("upper-intermediate", "B2"),
+    ("pre-intermediate", "A2"),
+    ("pre intermediate", "A2"),
+    ("intermediate", "B1"),
+    ("elementary", "A2"),
+    ("beginner", "A1"),
+    ("advanced", "C1"),
+    ("proficient", "C2"),
+    ("proficiency", "C2"),
+    ("mastery", "C2"),
+    ("native", "C2"),
+];
+
+static CEFR_CODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b([abc])\s?([12])\b").unwrap());
+
+/// Reads a CEFR level ("A1" to "C2") out of a free-text answer.
+pub fn parse_difficulty(answer: &str) -> Option<String> {
+    if let Some(captures) = CEFR_CODE.captures(answer) {
+        return Some(format!("{}{}", captures[1].to_uppercase(), &captures[2]));
+    }
+
+    let answer = answer.to_lowercase();
+    LEVEL_NAMES
+        .iter()
+        .filter_map(|(name, level)| answer.find(name).map(|position| (position, *level)))
+        .min_by_key(|(position, _)| *position)
+        .map(|(_, level)| level.to_string())
+}
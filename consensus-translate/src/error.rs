@@ -0,0 +1,89 @@
+use crate::{FailureClass, SourceError};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum TranslationError {
+    NoValidTranslations,
+    DuplicateSource(String),
+    /// The configured sources can't make a run, e.g. an empty `translate_sources`.
+    InvalidSources(SourceError),
+    Evaluation(String),
+    Extraction(String),
+    InvalidOutput(String),
+    BudgetExceeded {
+        spent_thousandths_cent: u64,
+        limit_thousandths_cent: u64,
+    },
+    /// `max_total_tokens` would be, or was, exceeded.
+    TokenBudgetExceeded {
+        used_tokens: u32,
+        estimated_tokens: u32,
+        limit_tokens: u32,
+    },
+    LowAgreement {
+        score: f32,
+    },
+    /// A translate source failed under `FailurePolicy::FailFast` or `FailurePolicy::Retry`.
+    SourceFailed {
+        model: String,
+        class: FailureClass,
+        message: String,
+    },
+    /// A batch item that wasn't started, with why.
+    Skipped(String),
+}
+
+impl fmt::Display for TranslationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranslationError::NoValidTranslations => {
+                f.write_str("No valid translations after filtering")
+            }
+            TranslationError::DuplicateSource(model) => {
+                write!(f, "Translation source {} is listed more than once", model)
+            }
+            TranslationError::InvalidSources(e) => write!(f, "Invalid sources: {}", e),
+            TranslationError::Evaluation(e) => write!(f, "Evaluation error: {}", e),
+            TranslationError::Extraction(message) => f.write_str(message),
+            TranslationError::InvalidOutput(message) => {
+                write!(f, "Synthesized translation rejected: {}", message)
+            }
+            TranslationError::BudgetExceeded {
+                spent_thousandths_cent,
+                limit_thousandths_cent,
+            } => write!(
+                f,
+                "Spent {} thousandths of a cent before the eval, over the limit of {}",
+                spent_thousandths_cent, limit_thousandths_cent
+            ),
+            TranslationError::TokenBudgetExceeded {
+                used_tokens,
+                estimated_tokens: 0,
+                limit_tokens,
+            } => write!(
+                f,
+                "Used {} tokens, over the limit of {}",
+                used_tokens, limit_tokens
+            ),
+            TranslationError::TokenBudgetExceeded {
+                used_tokens,
+                estimated_tokens,
+                limit_tokens,
+            } => write!(
+                f,
+                "Used {} tokens and the next call needs about {} more, over the limit of {}",
+                used_tokens, estimated_tokens, limit_tokens
+            ),
+            TranslationError::LowAgreement { score } => {
+                write!(f, "Translations agree too little (score {:.2})", score)
+            }
+            TranslationError::SourceFailed { model, message, .. } => {
+                write!(f, "Translation source {} failed: {}", model, message)
+            }
+            TranslationError::Skipped(reason) => write!(f, "Not translated, as {}", reason),
+        }
+    }
+}
+
+impl Error for TranslationError {}
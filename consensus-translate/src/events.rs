@@ -0,0 +1,63 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq, Hash)]
+pub enum FailureClass {
+    Timeout,
+    RateLimit,
+    Http4xx,
+    Http5xx,
+    Network,
+    /// The body ended early, most likely a dropped connection.
+    Truncated,
+    /// A complete response that couldn't be understood.
+    ParseError,
+    Refusal,
+    Other,
+}
+
+impl FailureClass {
+    /// Whether trying again could succeed.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            FailureClass::Timeout
+                | FailureClass::RateLimit
+                | FailureClass::Http5xx
+                | FailureClass::Network
+                | FailureClass::Truncated
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum TranslationEvent {
+    SourceFailed {
+        model: String,
+        class: FailureClass,
+        message: String,
+    },
+    /// Emitted once per run after all sources have finished, with how many failed per class.
+    FailureCounts(HashMap<FailureClass, u32>),
+}
+
+#[derive(Clone)]
+pub struct EventCallback(Arc<dyn Fn(&TranslationEvent) + Send + Sync>);
+
+impl EventCallback {
+    pub fn new(callback: impl Fn(&TranslationEvent) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(callback))
+    }
+
+    pub fn emit(&self, event: TranslationEvent) {
+        (self.0)(&event)
+    }
+}
+
+impl fmt::Debug for EventCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EventCallback")
+    }
+}
@@ -0,0 +1,44 @@
+use regex::Regex;
+
+/// A rewrite or check run on the synthesized text, redactions first.
+#[derive(Clone, Debug)]
+pub enum OutputFilter {
+    /// `replacement` may use `$1` or `$name`.
+    Redact { pattern: Regex, replacement: String },
+    /// Matches in the input must appear verbatim in the output.
+    Preserve(Regex),
+}
+
+// Returns the filtered output and the preserved matches missing from it.
+pub fn apply_output_filters(
+    filters: &[OutputFilter],
+    source: &str,
+    output: &str,
+) -> (String, Vec<String>) {
+    let mut output = output.to_string();
+    for filter in filters {
+        if let OutputFilter::Redact {
+            pattern,
+            replacement,
+        } = filter
+        {
+            output = pattern
+                .replace_all(&output, replacement.as_str())
+                .into_owned();
+        }
+    }
+
+    let mut missing: Vec<String> = Vec::new();
+    for filter in filters {
+        if let OutputFilter::Preserve(pattern) = filter {
+            for found in pattern.find_iter(source) {
+                let found = found.as_str();
+                if !output.contains(found) && !missing.iter().any(|m| m == found) {
+                    missing.push(found.to_string());
+                }
+            }
+        }
+    }
+
+    (output, missing)
+}
@@ -1,24 +1,141 @@
-use crate::{languages::Language, TranslationSource};
+use crate::{languages::Language, OpenRouterClient, Priority, TranslationSource};
+use std::error::Error;
+use std::fmt;
 
-const GPT4O: &'static str = "openai/gpt-4o-2024-11-20";
-const GPT41: &'static str = "openai/gpt-4.1";
-const GEMINI_FLASH2_5: &'static str = "google/gemini-2.5-flash";
-const LLAMA33_70B: &'static str = "meta-llama/llama-3.3-70b-instruct";
-const LLAMA4MAV: &'static str = "meta-llama/llama-4-maverick";
-const DEEPSEEKV3: &'static str = "deepseek/deepseek-chat-v3-0324";
-const SONNET4: &'static str = "anthropic/claude-sonnet-4";
-const OPUS4: &'static str = "anthropic/claude-opus-4";
+const GPT4O: &str = "openai/gpt-4o-2024-11-20";
+const GPT41: &str = "openai/gpt-4.1";
+const GEMINI_FLASH2_5: &str = "google/gemini-2.5-flash";
+const LLAMA33_70B: &str = "meta-llama/llama-3.3-70b-instruct";
+const LLAMA4MAV: &str = "meta-llama/llama-4-maverick";
+const DEEPSEEKV3: &str = "deepseek/deepseek-chat-v3-0324";
+const SONNET4: &str = "anthropic/claude-sonnet-4";
+const OPUS4: &str = "anthropic/claude-opus-4";
 
-const GEMMA3_27B: &'static str = "google/gemma-3-27b-it";
-const GROK3: &'static str = "x-ai/grok-3-beta";
+#[allow(dead_code)]
+const GEMMA3_27B: &str = "google/gemma-3-27b-it";
+const GROK3: &str = "x-ai/grok-3-beta";
 
+/// The models a run uses: the translate sources and the eval source that combines their output.
+#[derive(Clone, Debug)]
 pub struct SourceResponse {
     pub translate_sources: Vec<TranslationSource>,
     pub eval_source: TranslationSource,
 }
 
+impl SourceResponse {
+    pub fn builder() -> SourceResponseBuilder {
+        SourceResponseBuilder::default()
+    }
+}
+
+/// Why a [`SourceResponseBuilder`] couldn't build.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceError {
+    /// No translate sources were added, so every run would fail with no translations.
+    NoTranslateSources,
+    NoEvalSource,
+    /// The eval source has no models to prompt, e.g. DeepL.
+    EvalSourceCantEval(String),
+    /// With `require_known_pricing`, a model whose cost would be reported as zero.
+    UnknownPricing(String),
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::NoTranslateSources => f.write_str("No translate sources given"),
+            SourceError::NoEvalSource => f.write_str("No eval source given"),
+            SourceError::EvalSourceCantEval(model) => {
+                write!(f, "{} can't be used as the eval source", model)
+            }
+            SourceError::UnknownPricing(model) => write!(f, "No known pricing for {}", model),
+        }
+    }
+}
+
+impl Error for SourceError {}
+
+/// Builds and validates a [`SourceResponse`].
+#[derive(Clone, Debug, Default)]
+pub struct SourceResponseBuilder {
+    translate_sources: Vec<TranslationSource>,
+    eval_source: Option<TranslationSource>,
+    require_known_pricing: bool,
+}
+
+impl SourceResponseBuilder {
+    pub fn translate_source(mut self, source: TranslationSource) -> Self {
+        self.translate_sources.push(source);
+        self
+    }
+
+    pub fn translate_sources(
+        mut self,
+        sources: impl IntoIterator<Item = TranslationSource>,
+    ) -> Self {
+        self.translate_sources.extend(sources);
+        self
+    }
+
+    pub fn eval_source(mut self, source: TranslationSource) -> Self {
+        self.eval_source = Some(source);
+        self
+    }
+
+    /// Rejects OpenRouter models without a known price.
+    pub fn require_known_pricing(mut self, require_known_pricing: bool) -> Self {
+        self.require_known_pricing = require_known_pricing;
+        self
+    }
+
+    pub fn build(self) -> Result<SourceResponse, SourceError> {
+        if self.translate_sources.is_empty() {
+            return Err(SourceError::NoTranslateSources);
+        }
+        let eval_source = self.eval_source.ok_or(SourceError::NoEvalSource)?;
+        if eval_source.models().is_empty() {
+            return Err(SourceError::EvalSourceCantEval(
+                eval_source.model_name().to_string(),
+            ));
+        }
+        if self.require_known_pricing {
+            let unpriced = self
+                .translate_sources
+                .iter()
+                .chain(std::iter::once(&eval_source))
+                .flat_map(|source| source.models())
+                .find(|model| OpenRouterClient::known_pricing(model).is_none());
+            if let Some(model) = unpriced {
+                return Err(SourceError::UnknownPricing(model.to_string()));
+            }
+        }
+        Ok(SourceResponse {
+            translate_sources: self.translate_sources,
+            eval_source,
+        })
+    }
+}
+
+// A cheap model for small side questions about a translation.
+pub fn get_assessment_source() -> TranslationSource {
+    TranslationSource::Openrouter(GEMINI_FLASH2_5)
+}
+
 pub fn get_appropriate_sources(target_lang: Language) -> SourceResponse {
     match target_lang {
+        // Arabic, Hebrew and Hindi: GPT-4.1, GPT-4o, Sonnet and Gemini handle the scripts well,
+        // including diacritics and right-to-left punctuation. The Llama models drop into
+        // transliteration or mix in English more often, so they're left out.
+        Language::Arabic | Language::ArabicStandard => SourceResponse {
+            translate_sources: vec![
+                TranslationSource::Openrouter(GPT41),
+                TranslationSource::Openrouter(SONNET4),
+                TranslationSource::Openrouter(GPT4O),
+                TranslationSource::Openrouter(GEMINI_FLASH2_5),
+                TranslationSource::Openrouter(DEEPSEEKV3),
+            ],
+            eval_source: TranslationSource::Openrouter(GPT41),
+        },
         Language::Chinese | Language::ChineseTraditional => SourceResponse {
             translate_sources: vec![
                 TranslationSource::Openrouter(GPT4O),
@@ -61,6 +178,25 @@ pub fn get_appropriate_sources(target_lang: Language) -> SourceResponse {
             ],
             eval_source: TranslationSource::Openrouter(GPT41),
         },
+        Language::Hebrew => SourceResponse {
+            translate_sources: vec![
+                TranslationSource::Openrouter(GPT41),
+                TranslationSource::Openrouter(SONNET4),
+                TranslationSource::Openrouter(GPT4O),
+                TranslationSource::Openrouter(GEMINI_FLASH2_5),
+            ],
+            eval_source: TranslationSource::Openrouter(SONNET4),
+        },
+        Language::Hindi => SourceResponse {
+            translate_sources: vec![
+                TranslationSource::Openrouter(GEMINI_FLASH2_5),
+                TranslationSource::Openrouter(GPT41),
+                TranslationSource::Openrouter(SONNET4),
+                TranslationSource::Openrouter(GPT4O),
+                TranslationSource::Openrouter(GROK3),
+            ],
+            eval_source: TranslationSource::Openrouter(GPT41),
+        },
         Language::Hungarian => SourceResponse {
             translate_sources: vec![
                 TranslationSource::Openrouter(GPT4O),
@@ -152,7 +288,7 @@ pub fn get_appropriate_sources(target_lang: Language) -> SourceResponse {
             ],
             eval_source: TranslationSource::Openrouter(GPT41),
         },
-        Language::Unknown | _ => SourceResponse {
+        _ => SourceResponse {
             translate_sources: vec![
                 //TranslationSource::Openrouter(GEMMA3_27B),
                 TranslationSource::Openrouter(SONNET4),
@@ -165,3 +301,53 @@ pub fn get_appropriate_sources(target_lang: Language) -> SourceResponse {
         },
     }
 }
+
+// How many translate models `Priority::Speed` keeps.
+const SPEED_SOURCES: usize = 3;
+
+// Typical latency order, fastest first. Unlisted models sort last.
+const FASTEST_FIRST: &[&str] = &[
+    GEMINI_FLASH2_5,
+    LLAMA33_70B,
+    LLAMA4MAV,
+    GPT41,
+    GPT4O,
+    DEEPSEEKV3,
+    GROK3,
+    SONNET4,
+    OPUS4,
+];
+
+pub fn prioritize_sources(sources: SourceResponse, priority: Priority) -> SourceResponse {
+    match priority {
+        Priority::Speed => {
+            let mut translate_sources = sources.translate_sources;
+            translate_sources.sort_by_key(|source| {
+                FASTEST_FIRST
+                    .iter()
+                    .position(|model| *model == source.model_name())
+                    .unwrap_or(FASTEST_FIRST.len())
+            });
+            translate_sources.truncate(SPEED_SOURCES);
+            SourceResponse {
+                translate_sources,
+                eval_source: TranslationSource::Openrouter(GEMINI_FLASH2_5),
+            }
+        }
+        Priority::Balanced => sources,
+        // Sonnet joins pools that lack it, and Opus evaluates.
+        Priority::Quality => {
+            let mut translate_sources = sources.translate_sources;
+            if !translate_sources
+                .iter()
+                .any(|source| source.models().contains(&SONNET4))
+            {
+                translate_sources.push(TranslationSource::Openrouter(SONNET4));
+            }
+            SourceResponse {
+                translate_sources,
+                eval_source: TranslationSource::Openrouter(OPUS4),
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use crate::Language;
+
+/// Advice for commonly mistranslated pairs, as (source, target, guidance).
+pub const PAIR_GUIDANCE: &[(Language, Language, &str)] = &[
+    (
+        Language::Japanese,
+        Language::English,
+        "Convey honorifics and keigo through tone and word choice rather than keeping suffixes like -san or -sama, except where the text is clearly about names. Japanese often omits subjects; supply them from context.",
+    ),
+    (
+        Language::German,
+        Language::English,
+        "Break long compound nouns into natural English phrases rather than stacking nouns. Watch for separable verb prefixes at the end of the clause, which change the verb's meaning.",
+    ),
+    (
+        Language::English,
+        Language::German,
+        "Form compound nouns as German does rather than using loose phrases, and keep the verb in second position in main clauses and at the end of subordinate ones.",
+    ),
+    (
+        Language::Chinese,
+        Language::English,
+        "Chinese doesn't mark tense or number; infer them from context and time words, and choose articles accordingly.",
+    ),
+    (
+        Language::Korean,
+        Language::English,
+        "Convey the speech level through tone rather than literal markers. Korean often omits subjects and objects; supply them from context.",
+    ),
+    (
+        Language::Russian,
+        Language::English,
+        "Russian has no articles; choose a, an or the from context. Reflect verb aspect through English tense and phrasing, e.g. completed versus ongoing actions.",
+    ),
+    (
+        Language::English,
+        Language::Russian,
+        "Choose perfective or imperfective verb aspect deliberately, according to whether the action is completed, and keep cases consistent with prepositions.",
+    ),
+];
+
+// The first entry for the pair.
+pub fn find_pair_guidance(
+    table: &[(Language, Language, impl AsRef<str>)],
+    source_lang: Language,
+    target_lang: Language,
+) -> Option<&str> {
+    table
+        .iter()
+        .find(|(source, target, _)| *source == source_lang && *target == target_lang)
+        .map(|(_, _, guidance)| guidance.as_ref())
+}
@@ -0,0 +1,27 @@
+use crate::TranslationSource;
+use std::fmt;
+use std::sync::Arc;
+
+type ResolveFn = dyn Fn(&TranslationSource) -> Option<String> + Send + Sync;
+
+/// Picks the API key per source; None falls back to the default key.
+#[derive(Clone)]
+pub struct KeyResolver(Arc<ResolveFn>);
+
+impl KeyResolver {
+    pub fn new(
+        resolver: impl Fn(&TranslationSource) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self(Arc::new(resolver))
+    }
+
+    pub fn resolve(&self, source: &TranslationSource) -> Option<String> {
+        (self.0)(source)
+    }
+}
+
+impl fmt::Debug for KeyResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("KeyResolver")
+    }
+}
@@ -1,6 +1,58 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug)]
+/// A writing system, as used by [`Language::script`].
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Debug)]
+pub enum Script {
+    Latin,
+    Arabic,
+    Hebrew,
+    Greek,
+    Cyrillic,
+    Devanagari,
+    Thai,
+    /// Chinese characters, also used alongside kana in Japanese and occasionally in Korean.
+    Han,
+    /// Japanese hiragana and katakana.
+    Kana,
+    Hangul,
+}
+
+/// What the caller knows about the language of the text being translated.
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug, Default)]
+pub enum SourceLang {
+    /// Given to the models in the prompts, and used to pick them.
+    Known(Language),
+    /// Detected only to pick models.
+    #[default]
+    Detect,
+    /// Not guessed either, so the general-purpose models are used.
+    Unspecified,
+}
+
+impl SourceLang {
+    /// The language, when it's `Known`.
+    pub fn known(&self) -> Option<Language> {
+        match self {
+            SourceLang::Known(lang) => Some(*lang),
+            SourceLang::Detect | SourceLang::Unspecified => None,
+        }
+    }
+}
+
+impl From<Language> for SourceLang {
+    fn from(lang: Language) -> Self {
+        SourceLang::Known(lang)
+    }
+}
+
+/// For the old `Option<Language>` setting, where None meant guessing for the models.
+impl From<Option<Language>> for SourceLang {
+    fn from(lang: Option<Language>) -> Self {
+        lang.map_or(SourceLang::Detect, SourceLang::Known)
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Debug, Default)]
 pub enum Language {
     Arabic,
     ArabicStandard,
@@ -44,6 +96,7 @@ pub enum Language {
     Klingon,
     Thai,
     Welsh,
+    #[default]
     English,
     Unknown,
 }
@@ -97,4 +150,175 @@ impl Language {
             Language::Unknown => "an unspecified language".to_string(),
         }
     }
+
+    /// The language's name in its own language and script, for display. Empty for `Unknown`.
+    pub fn native_name(&self) -> &'static str {
+        match self {
+            Language::Klingon => "tlhIngan Hol",
+            Language::Thai => "ไทย",
+            Language::Welsh => "Cymraeg",
+            Language::Arabic => "العربية",
+            Language::ArabicStandard => "العربية الفصحى",
+            Language::Bulgarian => "Български",
+            Language::Chinese => "简体中文",
+            Language::ChineseTraditional => "繁體中文",
+            Language::Croatian => "Hrvatski",
+            Language::Czech => "Čeština",
+            Language::Danish => "Dansk",
+            Language::Dutch => "Nederlands",
+            Language::Esperanto => "Esperanto",
+            Language::Estonian => "Eesti",
+            Language::Finnish => "Suomi",
+            Language::French => "Français",
+            Language::German => "Deutsch",
+            Language::Greek => "Ελληνικά",
+            Language::Hebrew => "עברית",
+            Language::Hindi => "हिन्दी",
+            Language::Hungarian => "Magyar",
+            Language::Indonesian => "Bahasa Indonesia",
+            Language::Italian => "Italiano",
+            Language::Japanese => "日本語",
+            Language::Korean => "한국어",
+            Language::LatinClassical => "Lingua Latina",
+            Language::Latvian => "Latviešu",
+            Language::Lithuanian => "Lietuvių",
+            Language::Norwegian => "Norsk",
+            Language::Persian => "فارسی",
+            Language::Polish => "Polski",
+            Language::PortugueseBrazil => "Português (Brasil)",
+            Language::PortuguesePortugal => "Português (Portugal)",
+            Language::Romanian => "Română",
+            Language::Russian => "Русский",
+            Language::Slovakian => "Slovenčina",
+            Language::Slovenian => "Slovenščina",
+            Language::Spanish => "Español",
+            Language::Swedish => "Svenska",
+            Language::Turkish => "Türkçe",
+            Language::Ukrainian => "Українська",
+            Language::Vietnamese => "Tiếng Việt",
+            Language::English => "English",
+            Language::Unknown => "",
+        }
+    }
+
+    /// e.g. "Spanish (Mexican dialect)". Ignored unless `has_dialects`.
+    pub fn to_llm_format_with_dialect(&self, dialect: Option<&str>) -> String {
+        match dialect.map(str::trim) {
+            Some(dialect) if self.has_dialects() && !dialect.is_empty() => {
+                format!("{} ({} dialect)", self.to_llm_format(), dialect)
+            }
+            _ => self.to_llm_format(),
+        }
+    }
+
+    /// e.g. Mexican and Castilian Spanish.
+    pub fn has_dialects(&self) -> bool {
+        matches!(
+            self,
+            Language::Arabic
+                | Language::English
+                | Language::French
+                | Language::German
+                | Language::Spanish
+        )
+    }
+
+    /// Japanese counts as `Kana`. None for `Unknown`.
+    pub fn script(&self) -> Option<Script> {
+        match self {
+            Language::Arabic | Language::ArabicStandard | Language::Persian => Some(Script::Arabic),
+            Language::Hebrew => Some(Script::Hebrew),
+            Language::Greek => Some(Script::Greek),
+            Language::Bulgarian | Language::Russian | Language::Ukrainian => Some(Script::Cyrillic),
+            Language::Hindi => Some(Script::Devanagari),
+            Language::Thai => Some(Script::Thai),
+            Language::Chinese | Language::ChineseTraditional => Some(Script::Han),
+            Language::Japanese => Some(Script::Kana),
+            Language::Korean => Some(Script::Hangul),
+            Language::Croatian
+            | Language::Czech
+            | Language::Danish
+            | Language::Dutch
+            | Language::Esperanto
+            | Language::Estonian
+            | Language::Finnish
+            | Language::French
+            | Language::German
+            | Language::Hungarian
+            | Language::Indonesian
+            | Language::Italian
+            | Language::LatinClassical
+            | Language::Latvian
+            | Language::Lithuanian
+            | Language::Norwegian
+            | Language::Polish
+            | Language::PortugueseBrazil
+            | Language::PortuguesePortugal
+            | Language::Romanian
+            | Language::Slovakian
+            | Language::Slovenian
+            | Language::Spanish
+            | Language::Swedish
+            | Language::Turkish
+            | Language::Vietnamese
+            | Language::Klingon
+            | Language::Welsh
+            | Language::English => Some(Script::Latin),
+            Language::Unknown => None,
+        }
+    }
+
+    pub fn supports_well(&self) -> bool {
+        !matches!(self, Language::Klingon)
+    }
+
+    /// DeepL's target language code, or None where DeepL doesn't support the language.
+    #[cfg(feature = "deepl")]
+    pub fn to_deepl_code(&self) -> Option<&'static str> {
+        match self {
+            Language::Arabic | Language::ArabicStandard => Some("AR"),
+            Language::Bulgarian => Some("BG"),
+            Language::Chinese => Some("ZH-HANS"),
+            Language::ChineseTraditional => Some("ZH-HANT"),
+            Language::Czech => Some("CS"),
+            Language::Danish => Some("DA"),
+            Language::Dutch => Some("NL"),
+            Language::English => Some("EN-US"),
+            Language::Estonian => Some("ET"),
+            Language::Finnish => Some("FI"),
+            Language::French => Some("FR"),
+            Language::German => Some("DE"),
+            Language::Greek => Some("EL"),
+            Language::Hungarian => Some("HU"),
+            Language::Indonesian => Some("ID"),
+            Language::Italian => Some("IT"),
+            Language::Japanese => Some("JA"),
+            Language::Korean => Some("KO"),
+            Language::Latvian => Some("LV"),
+            Language::Lithuanian => Some("LT"),
+            Language::Norwegian => Some("NB"),
+            Language::Polish => Some("PL"),
+            Language::PortugueseBrazil => Some("PT-BR"),
+            Language::PortuguesePortugal => Some("PT-PT"),
+            Language::Romanian => Some("RO"),
+            Language::Russian => Some("RU"),
+            Language::Slovakian => Some("SK"),
+            Language::Slovenian => Some("SL"),
+            Language::Spanish => Some("ES"),
+            Language::Swedish => Some("SV"),
+            Language::Turkish => Some("TR"),
+            Language::Ukrainian => Some("UK"),
+            Language::Croatian
+            | Language::Esperanto
+            | Language::Hebrew
+            | Language::Hindi
+            | Language::LatinClassical
+            | Language::Persian
+            | Language::Vietnamese
+            | Language::Klingon
+            | Language::Thai
+            | Language::Welsh
+            | Language::Unknown => None,
+        }
+    }
 }
@@ -0,0 +1,123 @@
+use crate::backend::{BackendError, Completion, CompletionBackend, CompletionFuture};
+use crate::FailureClass;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Clone, Debug)]
+enum MockReply {
+    Text {
+        text: String,
+        cost: f64,
+        truncated: bool,
+    },
+    Error(BackendError),
+}
+
+/// A request the [`MockBackend`] received.
+#[derive(Clone, Debug)]
+pub struct MockCall {
+    pub model: String,
+    pub system_prompt: String,
+    pub main_prompt: String,
+}
+
+/// Canned replies per model, for tests. The eval reply needs a code block.
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    replies: HashMap<String, MockReply>,
+    latencies: HashMap<String, Duration>,
+    calls: Mutex<Vec<MockCall>>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `cost` is in dollars.
+    pub fn with_response(mut self, model: &str, text: &str, cost: f64) -> Self {
+        self.replies.insert(
+            model.to_string(),
+            MockReply::Text {
+                text: text.to_string(),
+                cost,
+                truncated: false,
+            },
+        );
+        self
+    }
+
+    /// Like `with_response`, but as if `model` stopped at its output length limit.
+    pub fn with_truncated_response(mut self, model: &str, text: &str, cost: f64) -> Self {
+        self.replies.insert(
+            model.to_string(),
+            MockReply::Text {
+                text: text.to_string(),
+                cost,
+                truncated: true,
+            },
+        );
+        self
+    }
+
+    pub fn with_error(mut self, model: &str, class: FailureClass, message: &str) -> Self {
+        self.replies.insert(
+            model.to_string(),
+            MockReply::Error(BackendError::new(class, message)),
+        );
+        self
+    }
+
+    /// Waits this long before replying, or failing, for `model`.
+    pub fn with_latency(mut self, model: &str, latency: Duration) -> Self {
+        self.latencies.insert(model.to_string(), latency);
+        self
+    }
+
+    /// Every request received so far, in order.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl CompletionBackend for MockBackend {
+    fn complete<'a>(
+        &'a self,
+        system_prompt: &'a str,
+        main_prompt: &'a str,
+        model: &'a str,
+        _temperature: f32,
+        _seed: Option<u64>,
+    ) -> CompletionFuture<'a> {
+        self.calls.lock().unwrap().push(MockCall {
+            model: model.to_string(),
+            system_prompt: system_prompt.to_string(),
+            main_prompt: main_prompt.to_string(),
+        });
+
+        Box::pin(async move {
+            if let Some(latency) = self.latencies.get(model) {
+                tokio::time::sleep(*latency).await;
+            }
+            match self.replies.get(model) {
+                Some(MockReply::Text {
+                    text,
+                    cost,
+                    truncated,
+                }) => Ok(Completion {
+                    model: model.to_string(),
+                    text: text.clone(),
+                    cost: *cost,
+                    truncated: *truncated,
+                    tokens: None,
+                }),
+                Some(MockReply::Error(e)) => Err(e.clone()),
+                None => Err(BackendError::new(
+                    FailureClass::Other,
+                    format!("MockBackend has no reply for {}", model),
+                )),
+            }
+        })
+    }
+}
@@ -1,23 +1,65 @@
-use reqwest::Client;
+use crate::backend::Completion;
+use crate::cost::{BilledUsage, CostBasis};
+use crate::ratelimit::RateLimiter;
+use crate::FailureClass;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
-use tracing::{debug, error, info, warn};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, error, warn};
 
 #[derive(Serialize)]
 struct ChatRequest {
     model: String,
+    // With more than one model OpenRouter falls back through them itself. Left out otherwise so
+    // that requests, and recordings of them, stay as they were.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    models: Vec<String>,
     messages: Vec<Message>,
     temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
 }
 
 #[derive(Serialize)]
 struct Message {
     role: String,
-    content: String,
+    content: MessageContent,
+}
+
+// Plain text serializes exactly as a string content did, so recordings made without caching
+// still replay.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+#[derive(Serialize)]
+struct ContentPart {
+    #[serde(rename = "type")]
+    part_type: String,
+    text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_control: Option<CacheControl>,
+}
+
+#[derive(Serialize)]
+struct CacheControl {
+    #[serde(rename = "type")]
+    cache_type: String,
 }
 
 #[derive(Deserialize)]
 struct ChatResponse {
+    // The model that served the request.
+    #[serde(default)]
+    model: Option<String>,
     #[serde(default)]
     choices: Vec<Choice>,
     #[serde(default)]
@@ -27,17 +69,63 @@ struct ChatResponse {
 #[derive(Deserialize)]
 struct Choice {
     message: MessageResponse,
+    // "length" when the model hit its output limit.
+    #[serde(default)]
+    finish_reason: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct MessageResponse {
-    content: String,
+    // Null from some providers when there's no text, e.g. after a content filter.
+    #[serde(default)]
+    content: Option<ResponseContent>,
+}
+
+// Some providers answer in the multimodal shape, a list of parts, rather than a string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ResponseContent {
+    Text(String),
+    Parts(Vec<ResponsePart>),
+}
+
+#[derive(Deserialize)]
+struct ResponsePart {
+    #[serde(rename = "type")]
+    part_type: String,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+impl ResponseContent {
+    // The text parts joined, or None if there are none.
+    fn text(&self) -> Option<String> {
+        match self {
+            ResponseContent::Text(text) => Some(text.clone()),
+            ResponseContent::Parts(parts) => {
+                let texts: Vec<&str> = parts
+                    .iter()
+                    .filter(|part| part.part_type == "text")
+                    .filter_map(|part| part.text.as_deref())
+                    .collect();
+                (!texts.is_empty()).then(|| texts.concat())
+            }
+        }
+    }
 }
 
 #[derive(Deserialize)]
 struct Usage {
     prompt_tokens: u32,
     completion_tokens: u32,
+    #[serde(default)]
+    prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+#[derive(Deserialize)]
+struct PromptTokensDetails {
+    #[serde(default)]
+    cached_tokens: u32,
 }
 
 #[derive(Deserialize)]
@@ -53,10 +141,155 @@ struct ErrorDetails {
     code: Option<i32>,
 }
 
+#[derive(Debug)]
+pub enum OpenRouterError {
+    Request(reqwest::Error),
+    Api { status: StatusCode, message: String },
+    InvalidResponse(String),
+    // The body ended mid-JSON, most likely a dropped connection rather than a bad response.
+    Truncated { bytes: usize },
+    // An HTML page or other non-JSON body, typically from a gateway or proxy during an outage.
+    NonJson { status: StatusCode, snippet: String },
+    Replay(String),
+}
+
+impl OpenRouterError {
+    pub fn failure_class(&self) -> FailureClass {
+        match self {
+            OpenRouterError::Request(e) if e.is_timeout() => FailureClass::Timeout,
+            OpenRouterError::Request(e) if e.is_decode() => FailureClass::ParseError,
+            OpenRouterError::Request(_) => FailureClass::Network,
+            OpenRouterError::Api { status, .. } if *status == StatusCode::TOO_MANY_REQUESTS => {
+                FailureClass::RateLimit
+            }
+            OpenRouterError::Api { status, .. } if status.is_client_error() => {
+                FailureClass::Http4xx
+            }
+            OpenRouterError::Api { status, .. } if status.is_server_error() => {
+                FailureClass::Http5xx
+            }
+            OpenRouterError::Api { .. } => FailureClass::Other,
+            OpenRouterError::NonJson { status, .. } if *status == StatusCode::TOO_MANY_REQUESTS => {
+                FailureClass::RateLimit
+            }
+            OpenRouterError::NonJson { status, .. } if status.is_client_error() => {
+                FailureClass::Http4xx
+            }
+            OpenRouterError::NonJson { status, .. } if status.is_server_error() => {
+                FailureClass::Http5xx
+            }
+            OpenRouterError::NonJson { .. } => FailureClass::ParseError,
+            OpenRouterError::InvalidResponse(_) => FailureClass::ParseError,
+            OpenRouterError::Truncated { .. } => FailureClass::Truncated,
+            OpenRouterError::Replay(_) => FailureClass::Other,
+        }
+    }
+}
+
+impl fmt::Display for OpenRouterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenRouterError::Request(e) => write!(f, "Request failed: {}", e),
+            OpenRouterError::Api { status, message } => {
+                write!(f, "OpenRouter API error: {} (status: {})", message, status)
+            }
+            OpenRouterError::InvalidResponse(message) => f.write_str(message),
+            OpenRouterError::Truncated { bytes } => {
+                write!(f, "Response truncated after {} bytes", bytes)
+            }
+            OpenRouterError::NonJson { status, snippet } => write!(
+                f,
+                "OpenRouter returned a non-JSON response (status: {}): {}",
+                status, snippet
+            ),
+            OpenRouterError::Replay(message) => write!(f, "Replay failed: {}", message),
+        }
+    }
+}
+
+impl Error for OpenRouterError {}
+
+impl From<reqwest::Error> for OpenRouterError {
+    fn from(e: reqwest::Error) -> Self {
+        OpenRouterError::Request(e)
+    }
+}
+
+/// Records exchanges to a directory, or replays identical requests from it.
+#[derive(Clone, Debug)]
+pub enum RecordMode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordedExchange {
+    request: serde_json::Value,
+    status: u16,
+    body: String,
+}
+
+// FNV-1a, as it's stable across Rust versions and platforms, unlike the std hashers.
+fn request_hash(request_json: &str) -> String {
+    let hash = request_json.bytes().fold(0xcbf29ce484222325u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+fn recording_path(dir: &Path, request_json: &str) -> PathBuf {
+    dir.join(format!("{}.json", request_hash(request_json)))
+}
+
+const TRUNCATION_RETRIES: u32 = 2;
+
+// How much of a non-JSON body to quote in the error.
+const SNIPPET_CHARS: usize = 200;
+
+// The start of `body` on one line, for quoting in errors.
+fn body_snippet(body: &str) -> String {
+    let collapsed = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    match collapsed.char_indices().nth(SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}…", &collapsed[..end]),
+        None => collapsed,
+    }
+}
+
+// Which of `models` the echoed slug `served` is. Compared without the ":free"-style variant
+// suffix, and when nothing matches exactly, the longest id `served` extends with a version
+// suffix, e.g. "openai/gpt-4.1-2025-04-14", so a short id doesn't claim a longer one's responses.
+fn served_model<'a>(served: &str, models: &[&'a str]) -> Option<&'a str> {
+    fn base(id: &str) -> &str {
+        id.split_once(':').map_or(id, |(base, _)| base)
+    }
+    let served = base(served);
+    models
+        .iter()
+        .find(|m| base(m) == served)
+        .or_else(|| {
+            models
+                .iter()
+                .filter(|m| {
+                    let id = base(m);
+                    served.len() > id.len()
+                        && served.starts_with(id)
+                        && served[id.len()..].starts_with('-')
+                })
+                .max_by_key(|m| base(m).len())
+        })
+        .copied()
+}
+
+#[derive(Clone)]
 pub struct OpenRouterClient {
     api_key: String,
     base_url: String,
     client: Client,
+    record_mode: Option<RecordMode>,
+    extra_headers: HeaderMap,
+    timeout: Option<Duration>,
+    prompt_caching: bool,
+    rate_limiter: Option<RateLimiter>,
 }
 
 impl OpenRouterClient {
@@ -65,29 +298,147 @@ impl OpenRouterClient {
             api_key: api_key.to_string(),
             base_url: "https://openrouter.ai/api/v1".to_string(),
             client: Client::new(),
+            record_mode: None,
+            extra_headers: HeaderMap::new(),
+            timeout: None,
+            prompt_caching: false,
+            rate_limiter: None,
         }
     }
 
-    fn calculate_cost(model: &str, prompt_tokens: u32, completion_tokens: u32) -> f64 {
-        let (input_price_per_million, output_price_per_million) = match model {
-            "openai/gpt-4o-2024-11-20" => (2.5, 10.0),
-            "openai/gpt-4.1" => (2.0, 8.0),
-            "google/gemini-2.5-flash" => (0.3, 2.5),
-            "meta-llama/llama-3.3-70b-instruct" => (0.1, 0.25),
-            "meta-llama/llama-4-maverick" => (0.15, 0.6),
-            "deepseek/deepseek-chat-v3-0324" => (0.3, 0.88),
-            "anthropic/claude-sonnet-4" => (3.0, 15.0),
-            "anthropic/claude-opus-4" => (15.0, 75.0),
-            "google/gemma-3-27b-it" => (0.1, 0.2),
-            "x-ai/grok-3-beta" => (3.0, 15.0),
-            _ => {
-                warn!("Unknown model '{}', defaulting to zero cost", model);
-                (0.0, 0.0)
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Waits on the limiter before every request, retries included. Replays aren't limited.
+    pub fn with_rate_limiter(mut self, rate_limiter: Option<RateLimiter>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Marks the system prompt cacheable, for Anthropic and Gemini.
+    pub fn with_prompt_caching(mut self, prompt_caching: bool) -> Self {
+        self.prompt_caching = prompt_caching;
+        self
+    }
+
+    fn system_content(&self, model: &str, system_prompt: &str) -> MessageContent {
+        let explicit_caching = model.starts_with("anthropic/") || model.starts_with("google/");
+        if !(self.prompt_caching && explicit_caching) {
+            return MessageContent::Text(system_prompt.to_string());
+        }
+
+        MessageContent::Parts(vec![ContentPart {
+            part_type: "text".to_string(),
+            text: system_prompt.to_string(),
+            cache_control: Some(CacheControl {
+                cache_type: "ephemeral".to_string(),
+            }),
+        }])
+    }
+
+    /// Extra headers; they override the client's own.
+    pub fn with_extra_headers(mut self, extra_headers: HeaderMap) -> Self {
+        self.extra_headers = extra_headers;
+        self
+    }
+
+    /// Keeps the pooled connections but authenticates as a different account.
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = api_key.to_string();
+        self
+    }
+
+    pub fn with_record_mode(mut self, record_mode: Option<RecordMode>) -> Self {
+        self.record_mode = record_mode;
+        self
+    }
+
+    /// Opens a pooled connection ahead of the first request.
+    pub async fn prewarm(&self) -> Result<(), OpenRouterError> {
+        if let Some(RecordMode::Replay(_)) = &self.record_mode {
+            return Ok(());
+        }
+
+        // The status doesn't matter, only that the handshake is done.
+        let response = self
+            .client
+            .head(format!("{}/models", self.base_url))
+            .headers(self.extra_headers.clone())
+            .send()
+            .await?;
+        debug!(
+            "Prewarmed OpenRouter connection, status: {}",
+            response.status()
+        );
+        Ok(())
+    }
+
+    fn replay(dir: &Path, request_json: &str) -> Result<(StatusCode, String), OpenRouterError> {
+        let path = recording_path(dir, request_json);
+        let recorded = fs::read_to_string(&path).map_err(|e| {
+            OpenRouterError::Replay(format!("No recording at {}: {}", path.display(), e))
+        })?;
+        let exchange: RecordedExchange = serde_json::from_str(&recorded).map_err(|e| {
+            OpenRouterError::Replay(format!("Invalid recording {}: {}", path.display(), e))
+        })?;
+        let status = StatusCode::from_u16(exchange.status).map_err(|e| {
+            OpenRouterError::Replay(format!("Invalid status in {}: {}", path.display(), e))
+        })?;
+
+        debug!("Replaying response from {}", path.display());
+        Ok((status, exchange.body))
+    }
+
+    fn record(dir: &Path, request_json: &str, status: StatusCode, body: &str) {
+        let path = recording_path(dir, request_json);
+        let exchange = RecordedExchange {
+            request: serde_json::from_str(request_json).unwrap_or(serde_json::Value::Null),
+            status: status.as_u16(),
+            body: body.to_string(),
+        };
+
+        let written = fs::create_dir_all(dir).and_then(|_| {
+            let json = serde_json::to_string_pretty(&exchange).map_err(std::io::Error::other)?;
+            fs::write(&path, json)
+        });
+        if let Err(e) = written {
+            warn!("Failed to record exchange to {}: {}", path.display(), e);
+        }
+    }
+
+    // Cache writes, which Anthropic bills at a premium, aren't reported and so aren't included.
+    pub(crate) fn pricing(model: &str) -> CostBasis {
+        Self::known_pricing(model).unwrap_or_else(|| {
+            warn!("Unknown model '{}', defaulting to zero cost", model);
+            CostBasis::PerToken {
+                input_per_million: 0.0,
+                cached_input_per_million: 0.0,
+                output_per_million: 0.0,
             }
+        })
+    }
+
+    pub(crate) fn known_pricing(model: &str) -> Option<CostBasis> {
+        let (input_per_million, cached_input_per_million, output_per_million) = match model {
+            "openai/gpt-4o-2024-11-20" => (2.5, 1.25, 10.0),
+            "openai/gpt-4.1" => (2.0, 0.5, 8.0),
+            "google/gemini-2.5-flash" => (0.3, 0.075, 2.5),
+            "meta-llama/llama-3.3-70b-instruct" => (0.1, 0.1, 0.25),
+            "meta-llama/llama-4-maverick" => (0.15, 0.15, 0.6),
+            "deepseek/deepseek-chat-v3-0324" => (0.3, 0.3, 0.88),
+            "anthropic/claude-sonnet-4" => (3.0, 0.3, 15.0),
+            "anthropic/claude-opus-4" => (15.0, 1.5, 75.0),
+            "google/gemma-3-27b-it" => (0.1, 0.1, 0.2),
+            "x-ai/grok-3-beta" => (3.0, 0.75, 15.0),
+            _ => return None,
         };
-        let input_cost = (prompt_tokens as f64 * input_price_per_million) / 1_000_000.0;
-        let output_cost = (completion_tokens as f64 * output_price_per_million) / 1_000_000.0;
-        input_cost + output_cost
+        Some(CostBasis::PerToken {
+            input_per_million,
+            cached_input_per_million,
+            output_per_million,
+        })
     }
 
     pub async fn complete(
@@ -96,48 +447,138 @@ impl OpenRouterClient {
         main_prompt: &str,
         model: &str,
         temperature: f32,
-    ) -> Result<(String, f64), Box<dyn Error>> {
+        seed: Option<u64>,
+    ) -> Result<(String, f64), OpenRouterError> {
+        self.complete_any(system_prompt, main_prompt, &[model], temperature, seed)
+            .await
+            .map(|completion| (completion.text, completion.cost))
+    }
+
+    /// Lets OpenRouter use the first available model.
+    pub async fn complete_any(
+        &self,
+        system_prompt: &str,
+        main_prompt: &str,
+        models: &[&str],
+        temperature: f32,
+        seed: Option<u64>,
+    ) -> Result<Completion, OpenRouterError> {
+        let Some(model) = models.first().copied() else {
+            return Err(OpenRouterError::InvalidResponse(
+                "No models to try".to_string(),
+            ));
+        };
         let url = format!("{}/chat/completions", self.base_url);
         let request_body = ChatRequest {
             model: model.to_string(),
+            models: if models.len() > 1 {
+                models.iter().map(|m| m.to_string()).collect()
+            } else {
+                Vec::new()
+            },
             messages: vec![
                 Message {
                     role: "system".to_string(),
-                    content: system_prompt.to_string(),
+                    content: self.system_content(model, system_prompt),
                 },
                 Message {
                     role: "user".to_string(),
-                    content: main_prompt.to_string(),
+                    content: MessageContent::Text(main_prompt.to_string()),
                 },
             ],
             temperature,
+            seed,
         };
         debug!(
-            "Sending request to OpenRouter: url={}, model={}, system_prompt='{}', main_prompt='{}'",
-            url, model, system_prompt, main_prompt
+            "Sending request to OpenRouter: url={}, models={:?}, system_prompt='{}', main_prompt='{}'",
+            url, models, system_prompt, main_prompt
         );
 
-        let response = self
-            .client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&request_body)
-            .send()
-            .await?;
+        let request_json = serde_json::to_string(&request_body)
+            .map_err(|e| OpenRouterError::InvalidResponse(format!("Invalid request: {}", e)))?;
+
+        let mut retries = 0;
+        loop {
+            match self.send(&url, &request_json, models).await {
+                Err(OpenRouterError::Truncated { bytes }) if retries < TRUNCATION_RETRIES => {
+                    retries += 1;
+                    warn!(
+                        "Response from {} looks truncated after {} bytes, retrying ({}/{})",
+                        model, bytes, retries, TRUNCATION_RETRIES
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send(
+        &self,
+        url: &str,
+        request_json: &str,
+        models: &[&str],
+    ) -> Result<Completion, OpenRouterError> {
+        // Recordings don't keep headers, so replays go by the body alone.
+        let (status, raw_body, content_type) = match &self.record_mode {
+            Some(RecordMode::Replay(dir)) => {
+                let (status, raw_body) = Self::replay(dir, request_json)?;
+                (status, raw_body, None)
+            }
+            _ => {
+                // Any of the models may serve it, and which one isn't known until it has.
+                if let Some(rate_limiter) = &self.rate_limiter {
+                    rate_limiter.acquire_all(models).await;
+                }
+                let mut request = self
+                    .client
+                    .post(url)
+                    .header("Authorization", format!("Bearer {}", self.api_key))
+                    .header("Content-Type", "application/json")
+                    .headers(self.extra_headers.clone())
+                    .body(request_json.to_string());
+                if let Some(timeout) = self.timeout {
+                    request = request.timeout(timeout);
+                }
+                let response = request.send().await?;
+
+                let status = response.status();
+                debug!("Received response with status: {}", status);
+                let content_type = response
+                    .headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string);
 
-        let status = response.status();
-        debug!("Received response with status: {}", status);
+                let raw_body = response.text().await?;
 
-        let raw_body = response.text().await?;
+                if let Some(RecordMode::Record(dir)) = &self.record_mode {
+                    Self::record(dir, request_json, status, &raw_body);
+                }
+
+                (status, raw_body, content_type)
+            }
+        };
         debug!("Raw response body: {}", raw_body);
 
+        let html = content_type.is_some_and(|content_type| content_type.contains("html"));
+        if html || raw_body.trim_start().starts_with('<') {
+            warn!("Non-JSON response from OpenRouter: status={}", status);
+            return Err(OpenRouterError::NonJson {
+                status,
+                snippet: body_snippet(&raw_body),
+            });
+        }
+
         if !status.is_success() {
             let error_response: ErrorResponse = serde_json::from_str(&raw_body).map_err(|e| {
                 error!(
                     "Failed to parse error response: {}, raw_body: {}",
                     e, raw_body
                 );
-                format!("Invalid error response: {}", e)
+                OpenRouterError::Api {
+                    status,
+                    message: format!("Invalid error response: {}", e),
+                }
             })?;
             warn!(
                 "OpenRouter error: status={}, message='{}', type='{}', code={:?}",
@@ -146,37 +587,147 @@ impl OpenRouterClient {
                 error_response.error.error_type,
                 error_response.error.code
             );
-            return Err(format!(
-                "OpenRouter API error: {} (status: {})",
-                error_response.error.message, status
-            )
-            .into());
+            return Err(OpenRouterError::Api {
+                status,
+                message: error_response.error.message,
+            });
         }
 
         let chat_response: ChatResponse = serde_json::from_str(&raw_body).map_err(|e| {
+            if e.is_eof() {
+                // Complete-but-wrong JSON fails with a syntax or data error instead, and
+                // retrying that would just get the same answer again.
+                warn!(
+                    "ChatResponse ended early: {}, received {} bytes",
+                    e,
+                    raw_body.len()
+                );
+                return OpenRouterError::Truncated {
+                    bytes: raw_body.len(),
+                };
+            }
             error!(
                 "Failed to parse ChatResponse: {}, raw_body: {}",
                 e, raw_body
             );
-            format!("Error decoding response body: {}", e)
+            OpenRouterError::InvalidResponse(format!("Error decoding response body: {}", e))
         })?;
 
         if chat_response.choices.is_empty() {
             error!("No choices in response: {}", raw_body);
-            return Err("No choices returned from OpenRouter API".into());
+            return Err(OpenRouterError::InvalidResponse(
+                "No choices returned from OpenRouter API".to_string(),
+            ));
         }
 
-        let (prompt_tokens, completion_tokens) = chat_response
+        let (prompt_tokens, completion_tokens, cached_tokens) = chat_response
             .usage
             .as_ref()
-            .map(|u| (u.prompt_tokens, u.completion_tokens))
+            .map(|u| {
+                let cached_tokens = u
+                    .prompt_tokens_details
+                    .as_ref()
+                    .map_or(0, |details| details.cached_tokens);
+                (u.prompt_tokens, u.completion_tokens, cached_tokens)
+            })
             .unwrap_or_else(|| {
                 warn!("No usage data in response, defaulting tokens to 0");
-                (0, 0)
+                (0, 0, 0)
             });
+        if cached_tokens > 0 {
+            debug!(
+                "{} of {} prompt tokens were cached",
+                cached_tokens, prompt_tokens
+            );
+        }
 
-        let cost = Self::calculate_cost(model, prompt_tokens, completion_tokens);
+        let model = chat_response
+            .model
+            .as_deref()
+            .and_then(|served| served_model(served, models))
+            .unwrap_or(models[0])
+            .to_string();
 
-        Ok((chat_response.choices[0].message.content.clone(), cost))
+        let tokens = chat_response
+            .usage
+            .as_ref()
+            .map(|u| u.prompt_tokens + u.completion_tokens);
+
+        let cost = Self::pricing(&model).cost(&BilledUsage {
+            prompt_tokens,
+            completion_tokens,
+            cached_tokens,
+            characters: 0,
+        });
+
+        let choice = &chat_response.choices[0];
+        let truncated = choice.finish_reason.as_deref() == Some("length");
+        if truncated {
+            warn!("{} stopped at its output length limit", model);
+        }
+
+        let Some(text) = choice
+            .message
+            .content
+            .as_ref()
+            .and_then(ResponseContent::text)
+        else {
+            error!("No text content in response: {}", raw_body);
+            return Err(OpenRouterError::InvalidResponse(format!(
+                "No text content returned by {}",
+                model
+            )));
+        };
+
+        Ok(Completion {
+            model,
+            text,
+            cost,
+            truncated,
+            tokens,
+        })
+    }
+}
+
+// Leaves out the API key.
+impl fmt::Debug for OpenRouterClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpenRouterClient")
+            .field("base_url", &self.base_url)
+            .field("record_mode", &self.record_mode)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn served_model_prefers_the_exact_id() {
+        let models = ["openai/gpt-4.1", "openai/gpt-4.1-mini"];
+        assert_eq!(
+            served_model("openai/gpt-4.1-mini", &models),
+            Some("openai/gpt-4.1-mini")
+        );
+        assert_eq!(
+            served_model("openai/gpt-4.1", &models),
+            Some("openai/gpt-4.1")
+        );
+    }
+
+    #[test]
+    fn served_model_strips_suffixes() {
+        let models = ["openai/gpt-4.1", "openai/gpt-4.1-mini", "meta/llama:free"];
+        assert_eq!(
+            served_model("openai/gpt-4.1-mini-2025-04-14", &models),
+            Some("openai/gpt-4.1-mini")
+        );
+        assert_eq!(
+            served_model("openai/gpt-4.1-2025-04-14", &models),
+            Some("openai/gpt-4.1")
+        );
+        assert_eq!(served_model("meta/llama", &models), Some("meta/llama:free"));
+        assert_eq!(served_model("openai/gpt-4.10", &models), None);
     }
 }
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+struct Bucket {
+    requests_per_minute: u32,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn new(requests_per_minute: u32) -> Self {
+        Self {
+            requests_per_minute,
+            tokens: requests_per_minute as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    // Takes a token if there is one, otherwise says how long until there will be.
+    fn take(&mut self) -> Option<Duration> {
+        let per_second = self.requests_per_minute as f64 / 60.0;
+        let now = Instant::now();
+        self.tokens = (self.tokens
+            + now.duration_since(self.refilled_at).as_secs_f64() * per_second)
+            .min(self.requests_per_minute as f64);
+        self.refilled_at = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64((1.0 - self.tokens) / per_second))
+        }
+    }
+}
+
+#[derive(Default)]
+struct RateLimiterState {
+    limits: HashMap<String, u32>,
+    default_limit: Option<u32>,
+    buckets: HashMap<String, Bucket>,
+}
+
+// The part of the slug before the slash, e.g. "openai".
+fn provider_of(model: &str) -> &str {
+    model.split('/').next().unwrap_or(model)
+}
+
+/// Per-provider requests-per-minute token bucket. Clones share the budget.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    state: Arc<Mutex<RateLimiterState>>,
+}
+
+impl RateLimiter {
+    /// No limits; add them with `with_limit` and `with_default_limit`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits `provider` to `requests_per_minute`. Zero is treated as one.
+    pub fn with_limit(self, provider: &str, requests_per_minute: u32) -> Self {
+        {
+            let mut state = self.state.lock().unwrap();
+            state
+                .limits
+                .insert(provider.to_string(), requests_per_minute.max(1));
+            state.buckets.remove(provider);
+        }
+        self
+    }
+
+    /// The limit for providers without one of their own. Without it they're unlimited.
+    pub fn with_default_limit(self, requests_per_minute: u32) -> Self {
+        self.state.lock().unwrap().default_limit = Some(requests_per_minute.max(1));
+        self
+    }
+
+    /// Waits until a request to `model`'s provider is within its limit, and counts it.
+    pub async fn acquire(&self, model: &str) {
+        self.acquire_provider(provider_of(model)).await;
+    }
+
+    /// Like `acquire`, once for each distinct provider among `models`, for a request any of
+    /// them may serve.
+    pub async fn acquire_all(&self, models: &[&str]) {
+        let mut providers: Vec<&str> = Vec::new();
+        for model in models {
+            let provider = provider_of(model);
+            if !providers.contains(&provider) {
+                providers.push(provider);
+                self.acquire_provider(provider).await;
+            }
+        }
+    }
+
+    async fn acquire_provider(&self, provider: &str) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let Some(limit) = state.limits.get(provider).copied().or(state.default_limit)
+                else {
+                    return;
+                };
+                state
+                    .buckets
+                    .entry(provider.to_string())
+                    .or_insert_with(|| Bucket::new(limit))
+                    .take()
+            };
+            match wait {
+                None => return,
+                Some(wait) => {
+                    debug!("Rate limit for {} reached, waiting {:?}", provider, wait);
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for RateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("RateLimiter")
+            .field("limits", &state.limits)
+            .field("default_limit", &state.default_limit)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,133 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Grapheme Levenshtein distance over the longer length, 0.0 to 1.0.
+pub fn normalized_edit_distance(a: &str, b: &str) -> f32 {
+    let a: Vec<&str> = a.graphemes(true).collect();
+    let b: Vec<&str> = b.graphemes(true).collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 0.0;
+    }
+
+    // Single-row dynamic programming; `previous` holds the row above.
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_grapheme) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, b_grapheme) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_grapheme != b_grapheme);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()] as f32 / longest as f32
+}
+
+/// 1.0 minus [`normalized_edit_distance`].
+pub fn similarity(a: &str, b: &str) -> f32 {
+    1.0 - normalized_edit_distance(a, b)
+}
+
+/// Mean pairwise [`similarity`]; None for fewer than two texts.
+pub fn agreement_score(texts: &[&str]) -> Option<f32> {
+    let mut total = 0.0;
+    let mut pairs = 0;
+    for (i, a) in texts.iter().enumerate() {
+        for b in &texts[i + 1..] {
+            total += similarity(a, b);
+            pairs += 1;
+        }
+    }
+
+    (pairs > 0).then(|| total / pairs as f32)
+}
+
+/// The most central of `size` texts that are pairwise `threshold` similar.
+pub fn find_quorum(texts: &[&str], size: usize, threshold: f32) -> Option<usize> {
+    if size == 0 || texts.len() < size {
+        return None;
+    }
+
+    let n = texts.len();
+    let mut similarities = vec![vec![1.0; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let s = similarity(texts[i], texts[j]);
+            similarities[i][j] = s;
+            similarities[j][i] = s;
+        }
+    }
+
+    // Greedily grow a group around each text in turn. Pools are a handful of models, so this is
+    // cheap, and misses only contrived cases where a different grouping would have worked.
+    for anchor in 0..n {
+        let mut group = vec![anchor];
+        for candidate in (0..n).filter(|&candidate| candidate != anchor) {
+            if group
+                .iter()
+                .all(|&m| similarities[m][candidate] >= threshold)
+            {
+                group.push(candidate);
+            }
+        }
+        if group.len() >= size {
+            return group.iter().copied().max_by(|&a, &b| {
+                let closeness = |i: usize| group.iter().map(|&m| similarities[i][m]).sum::<f32>();
+                closeness(a).total_cmp(&closeness(b))
+            });
+        }
+    }
+
+    None
+}
+
+/// Indices of up to `count` mutually distinct texts, first always included.
+pub fn most_distinct(texts: &[&str], count: usize) -> Vec<usize> {
+    if texts.is_empty() || count == 0 {
+        return Vec::new();
+    }
+
+    let mut picked = vec![0];
+    // Each text's distance to the closest picked one.
+    let mut distances: Vec<f32> = texts
+        .iter()
+        .map(|text| normalized_edit_distance(text, texts[0]))
+        .collect();
+    while picked.len() < count.min(texts.len()) {
+        let Some(next) = (0..texts.len())
+            .filter(|i| !picked.contains(i))
+            .reduce(|best, i| {
+                if distances[i] > distances[best] {
+                    i
+                } else {
+                    best
+                }
+            })
+        else {
+            break;
+        };
+        picked.push(next);
+        for (i, text) in texts.iter().enumerate() {
+            distances[i] = distances[i].min(normalized_edit_distance(text, texts[next]));
+        }
+    }
+
+    picked.sort_unstable();
+    picked
+}
+
+// Each text's mean similarity to the others, as how central it is to the group. 0.0 for a text
+// with nothing to compare with.
+pub(crate) fn centrality(texts: &[&str]) -> Vec<f32> {
+    let mut totals = vec![0.0; texts.len()];
+    for i in 0..texts.len() {
+        for j in i + 1..texts.len() {
+            let s = similarity(texts[i], texts[j]);
+            totals[i] += s;
+            totals[j] += s;
+        }
+    }
+    let others = texts.len().saturating_sub(1).max(1) as f32;
+    totals.into_iter().map(|total| total / others).collect()
+}
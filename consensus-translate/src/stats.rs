@@ -0,0 +1,85 @@
+use crate::TranslationResponseItem;
+use serde::Serialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Counted in grapheme clusters and Unicode words.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TranslationStats {
+    pub source_chars: usize,
+    pub source_words: usize,
+    pub output_chars: usize,
+    pub output_words: usize,
+}
+
+impl TranslationStats {
+    pub fn new(source: &str, output: &str) -> Self {
+        Self {
+            source_chars: count_chars(source),
+            source_words: count_words(source),
+            output_chars: count_chars(output),
+            output_words: count_words(output),
+        }
+    }
+}
+
+pub fn count_chars(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+pub fn count_words(text: &str) -> usize {
+    text.unicode_words().count()
+}
+
+/// Quality signals for one source's translation.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CandidateReport {
+    pub model: String,
+    /// Length over the median candidate length. None for refusals.
+    pub length_ratio: Option<f32>,
+    /// See `TranslationResponseItem::distance_to_synthesis`.
+    pub distance_to_synthesis: Option<f32>,
+    /// The eval's score for it, from 0 to 10, where it gave one.
+    pub score: Option<f32>,
+    pub refused: bool,
+    pub truncated: bool,
+    pub duration_ms: Option<u32>,
+    /// In dollars.
+    pub cost: f64,
+}
+
+pub(crate) fn candidate_reports(
+    items: &[TranslationResponseItem],
+    ranking: &[(String, f32)],
+) -> Vec<CandidateReport> {
+    let mut lengths: Vec<usize> = items
+        .iter()
+        .filter(|item| item.flags.is_candidate())
+        .map(|item| count_chars(&item.text))
+        .collect();
+    lengths.sort_unstable();
+    let median = match lengths.len() {
+        0 => None,
+        n if n % 2 == 0 => Some((lengths[n / 2 - 1] + lengths[n / 2]) as f32 / 2.0),
+        n => Some(lengths[n / 2] as f32),
+    };
+
+    items
+        .iter()
+        .filter(|item| !item.flags.combined)
+        .map(|item| CandidateReport {
+            model: item.model.clone(),
+            length_ratio: median
+                .filter(|median| *median > 0.0 && !item.flags.refused)
+                .map(|median| count_chars(&item.text) as f32 / median),
+            distance_to_synthesis: item.distance_to_synthesis,
+            score: ranking
+                .iter()
+                .find(|(model, _)| *model == item.model)
+                .map(|(_, score)| *score),
+            refused: item.flags.refused,
+            truncated: item.flags.truncated,
+            duration_ms: item.duration_ms,
+            cost: item.cost,
+        })
+        .collect()
+}
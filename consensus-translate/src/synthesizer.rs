@@ -0,0 +1,52 @@
+use crate::{
+    GenerationSettings, ModelCall, PipelineContext, TranslationError, TranslationResponseItem,
+};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+
+/// What a [`Synthesizer`] produces from the candidates.
+#[derive(Clone, Debug)]
+pub struct Synthesis {
+    pub text: String,
+    /// Shown as "Synthesized (<model>)" on the combined item, e.g. the eval model's slug.
+    pub model: String,
+    /// In dollars.
+    pub cost: f64,
+    /// Scores per candidate model, if the synthesizer produces them.
+    pub ranking: Vec<(String, f32)>,
+    /// The system prompt sent, if any, for `TranslationConfig::include_prompts`.
+    pub prompt: Option<String>,
+    /// A more literal rendering, for `TranslationConfig::include_literal`.
+    pub literal: Option<String>,
+    /// What the eval was sent with, if it was a model call.
+    pub settings: Option<GenerationSettings>,
+    /// The model calls made, for `TranslationConfig::trace`. Fine to leave empty.
+    pub calls: Vec<ModelCall>,
+}
+
+pub type SynthesisFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Synthesis, TranslationError>> + Send + 'a>>;
+
+/// Merges the candidates into one; the eval model by default.
+pub trait Synthesizer: fmt::Debug + Send + Sync {
+    fn synthesize<'a>(
+        &'a self,
+        candidates: &'a [TranslationResponseItem],
+        ctx: &'a PipelineContext<'a>,
+    ) -> SynthesisFuture<'a>;
+}
+
+/// The default synthesizer, as a type so it can be wrapped or reused.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LlmSynthesizer;
+
+impl Synthesizer for LlmSynthesizer {
+    fn synthesize<'a>(
+        &'a self,
+        candidates: &'a [TranslationResponseItem],
+        ctx: &'a PipelineContext<'a>,
+    ) -> SynthesisFuture<'a> {
+        Box::pin(crate::run_eval(ctx, candidates, Default::default()))
+    }
+}
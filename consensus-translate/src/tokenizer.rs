@@ -0,0 +1,26 @@
+use crate::detect::script_of;
+use crate::Script;
+use std::fmt;
+
+/// Counts tokens for budgeting; [`HeuristicTokenizer`] by default.
+pub trait Tokenizer: fmt::Debug + Send + Sync {
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+/// Estimates from characters: ~4 a token for Latin, 1 for CJK, 2 otherwise.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        let tokens: f32 = text
+            .chars()
+            .map(|c| match script_of(c) {
+                Some(Script::Han | Script::Kana | Script::Hangul) => 1.0,
+                Some(Script::Latin) | None => 0.25,
+                Some(_) => 0.5,
+            })
+            .sum();
+        tokens.ceil() as usize
+    }
+}
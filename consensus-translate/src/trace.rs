@@ -0,0 +1,31 @@
+use serde::Serialize;
+
+/// One request to a model, as sent and as answered.
+#[derive(Clone, Serialize, Debug)]
+pub struct ModelCall {
+    pub model: String,
+    /// Empty for DeepL, which takes no prompt.
+    pub system_prompt: String,
+    pub user_prompt: String,
+    /// The response before any cleanup. None if the call failed.
+    pub raw_response: Option<String>,
+    pub error: Option<String>,
+    /// In dollars.
+    pub cost: f64,
+    /// None if the call never finished, e.g. at the deadline.
+    pub duration_ms: Option<u32>,
+}
+
+/// A machine-readable record of the calls behind a response.
+#[derive(Clone, Serialize, Debug, Default)]
+pub struct TranslationTrace {
+    /// Sources left out before any call, with why, e.g. "openai/gpt-4o: duplicate".
+    pub skipped_sources: Vec<String>,
+    pub translate_calls: Vec<ModelCall>,
+    /// As (model, reason).
+    pub rejected_outputs: Vec<(String, String)>,
+    /// Empty when the eval was skipped. A re-prompt for a missing code block is a second call.
+    pub eval_calls: Vec<ModelCall>,
+    /// The text taken from the eval response, before the newline policy and output filters.
+    pub extracted: Option<String>,
+}